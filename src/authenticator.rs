@@ -0,0 +1,371 @@
+use crate::otp::{decode_secret, Algorithm, Otp, OtpKind};
+use crate::*;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+
+const MIGRATION_PREFIX: &str = "otpauth-migration://offline?data=";
+
+// Minimal protobuf reader for the Google Authenticator export
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Skip,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos).ok_or("Migration data truncated")?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Migration data has an invalid varint".to_string())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or("Migration data truncated")?;
+        let bytes = self
+            .buf
+            .get(self.pos..end)
+            .ok_or("Migration data truncated")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    // Field number and value, None at the end
+    fn field(&mut self) -> Result<Option<(u64, Field<'a>)>, String> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 => self.take(8).map(|_| Field::Skip)?,
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => self.take(4).map(|_| Field::Skip)?,
+            wire => return Err(format!("Migration data has unknown wire type {}", wire)),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_varint(buf, field << 3 | 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_number(buf: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buf, field << 3);
+    write_varint(buf, value);
+}
+
+// The name may carry the issuer as "issuer:account"
+fn split_name(name: &str, issuer: Option<String>) -> (Option<String>, String) {
+    match (name.split_once(':'), issuer) {
+        (Some((prefix, account)), Some(issuer)) if prefix.trim() == issuer => {
+            (Some(issuer), account.trim().to_string())
+        }
+        (Some((prefix, account)), None) => (issuer_of(prefix), account.trim().to_string()),
+        (_, issuer) => (issuer, name.trim().to_string()),
+    }
+}
+
+fn issuer_of(value: &str) -> Option<String> {
+    Some(value.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn parse_parameters(buf: &[u8]) -> Result<Option<Otp>, String> {
+    let mut r = Reader::new(buf);
+    let (mut secret, mut name, mut issuer) = (vec![], String::new(), None);
+    let (mut algorithm, mut digits, mut kind, mut counter) = (1, 1, 2, 0);
+    while let Some((number, field)) = r.field()? {
+        match (number, field) {
+            (1, Field::Bytes(b)) => secret = b.to_vec(),
+            (2, Field::Bytes(b)) => name = String::from_utf8_lossy(b).to_string(),
+            (3, Field::Bytes(b)) => issuer = issuer_of(&String::from_utf8_lossy(b)),
+            (4, Field::Varint(v)) => algorithm = v,
+            (5, Field::Varint(v)) => digits = v,
+            (6, Field::Varint(v)) => kind = v,
+            (7, Field::Varint(v)) => counter = v,
+            _ => {}
+        }
+    }
+    let algorithm = match algorithm {
+        0 | 1 => Algorithm::Sha1,
+        2 => Algorithm::Sha256,
+        3 => Algorithm::Sha512,
+        // MD5 is not supported
+        _ => return Ok(None),
+    };
+    let kind = match kind {
+        1 => OtpKind::Hotp { counter },
+        _ => OtpKind::Totp { step: 30 },
+    };
+    let (issuer, account) = split_name(&name, issuer);
+    Ok(Some(Otp {
+        kind,
+        algorithm,
+        digits: if digits == 2 { 8 } else { 6 },
+        secret,
+        issuer,
+        account,
+    }))
+}
+
+// otpauth-migration://offline?data=<base64 protobuf>, and the number of MD5 entries skipped
+pub fn parse_migration(uri: &str) -> Result<(Vec<Otp>, usize), String> {
+    let data = uri
+        .trim()
+        .strip_prefix(MIGRATION_PREFIX)
+        .ok_or("Not an otpauth-migration URI")?;
+    let data = percent_decode_str(data).decode_utf8_lossy();
+    let data = base64::decode(data.as_bytes()).map_err(|_| "Invalid migration data")?;
+
+    let (mut otps, mut skipped) = (vec![], 0);
+    let mut r = Reader::new(&data);
+    while let Some((number, field)) = r.field()? {
+        if let (1, Field::Bytes(b)) = (number, field) {
+            match parse_parameters(b)? {
+                Some(otp) => otps.push(otp),
+                None => skipped += 1,
+            }
+        }
+    }
+    Ok((otps, skipped))
+}
+
+// Steam codes, other periods and 7 digits cannot be expressed and are skipped
+pub fn to_migration(otps: &[Otp]) -> (String, usize) {
+    let mut payload = vec![];
+    let mut skipped = 0;
+    for otp in otps {
+        let (kind, counter) = match otp.kind {
+            OtpKind::Totp { step: 30 } => (2, 0),
+            OtpKind::Hotp { counter } => (1, counter),
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        if otp.digits != 6 && otp.digits != 8 {
+            skipped += 1;
+            continue;
+        }
+        let mut params = vec![];
+        write_bytes(&mut params, 1, &otp.secret);
+        write_bytes(&mut params, 2, otp.account.as_bytes());
+        if let Some(issuer) = &otp.issuer {
+            write_bytes(&mut params, 3, issuer.as_bytes());
+        }
+        let algorithm = match otp.algorithm {
+            Algorithm::Sha1 => 1,
+            Algorithm::Sha256 => 2,
+            Algorithm::Sha512 => 3,
+        };
+        write_number(&mut params, 4, algorithm);
+        write_number(&mut params, 5, if otp.digits == 8 { 2 } else { 1 });
+        write_number(&mut params, 6, kind);
+        if counter > 0 {
+            write_number(&mut params, 7, counter);
+        }
+        write_bytes(&mut payload, 1, &params);
+        params.zeroize();
+    }
+    write_number(&mut payload, 2, 1);
+    write_number(&mut payload, 3, 1);
+    let data = base64::encode(&payload);
+    payload.zeroize();
+    let uri = format!(
+        "{}{}",
+        MIGRATION_PREFIX,
+        utf8_percent_encode(&data, NON_ALPHANUMERIC)
+    );
+    (uri, skipped)
+}
+
+fn parse_algorithm(value: &Value) -> Result<Algorithm, String> {
+    match value.as_str().unwrap_or("SHA1").to_uppercase().as_str() {
+        "SHA1" => Ok(Algorithm::Sha1),
+        "SHA256" => Ok(Algorithm::Sha256),
+        "SHA512" => Ok(Algorithm::Sha512),
+        other => Err(format!("Unsupported algorithm '{}'", other)),
+    }
+}
+
+fn parse_json_entry(
+    kind: &str,
+    info: &Value,
+    name: &str,
+    issuer: Option<String>,
+) -> Result<Otp, String> {
+    let secret = info["secret"].as_str().ok_or("Missing secret")?;
+    let (issuer, account) = split_name(name, issuer);
+    let period = info["period"].as_u64().unwrap_or(30);
+    let kind = match kind.to_lowercase().as_str() {
+        "totp" => OtpKind::Totp { step: period },
+        "hotp" => OtpKind::Hotp {
+            counter: info["counter"].as_u64().unwrap_or_default(),
+        },
+        "steam" => OtpKind::Steam,
+        other => return Err(format!("Unsupported type '{}'", other)),
+    };
+    Ok(Otp {
+        kind,
+        algorithm: parse_algorithm(&info["algo"])
+            .or_else(|_| parse_algorithm(&info["algorithm"]))?,
+        digits: u32::try_from(info["digits"].as_u64().unwrap_or(6))
+            .map_err(|_| "Invalid digits".to_string())?,
+        secret: decode_secret(secret)?,
+        issuer,
+        account,
+    })
+}
+
+// Aegis exports are an object with `db`, andOTP exports are an array
+pub fn parse_json(text: &str) -> Result<Vec<Otp>, String> {
+    let value = serde_json::from_str::<Value>(text).map_err(|err| err.to_string())?;
+    let mut otps = vec![];
+    match &value {
+        Value::Object(map) if map.contains_key("db") => {
+            let entries = map["db"]["entries"]
+                .as_array()
+                .ok_or("Encrypted Aegis exports are not supported, export without a password")?;
+            for entry in entries {
+                let kind = entry["type"].as_str().unwrap_or_default();
+                let name = entry["name"].as_str().unwrap_or_default();
+                let issuer = issuer_of(entry["issuer"].as_str().unwrap_or_default());
+                otps.push(parse_json_entry(kind, &entry["info"], name, issuer)?);
+            }
+        }
+        Value::Array(entries) => {
+            for entry in entries {
+                let kind = entry["type"].as_str().unwrap_or_default();
+                let name = entry["label"].as_str().unwrap_or_default();
+                let issuer = issuer_of(entry["issuer"].as_str().unwrap_or_default());
+                otps.push(parse_json_entry(kind, entry, name, issuer)?);
+            }
+        }
+        _ => return Err("Unknown export format, expected Aegis or andOTP JSON".to_string()),
+    }
+    Ok(otps)
+}
+
+fn uuid() -> String {
+    let mut b = random_bytes::<16>();
+    b[6] = b[6] & 0x0f | 0x40;
+    b[8] = b[8] & 0x3f | 0x80;
+    let h = hex::encode(b);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &h[..8],
+        &h[8..12],
+        &h[12..16],
+        &h[16..20],
+        &h[20..]
+    )
+}
+
+// Unencrypted Aegis vault
+pub fn to_aegis(otps: &[Otp]) -> String {
+    let entries = otps
+        .iter()
+        .map(|otp| {
+            let (kind, period, counter) = match otp.kind {
+                OtpKind::Totp { step } => ("totp", step, None),
+                OtpKind::Hotp { counter } => ("hotp", 30, Some(counter)),
+                OtpKind::Steam => ("steam", 30, None),
+            };
+            let algo = match otp.algorithm {
+                Algorithm::Sha1 => "SHA1",
+                Algorithm::Sha256 => "SHA256",
+                Algorithm::Sha512 => "SHA512",
+            };
+            let mut info = json!({
+                "secret": base32::encode(base32::Alphabet::RFC4648 { padding: false }, &otp.secret),
+                "algo": algo,
+                "digits": otp.digits,
+                "period": period,
+            });
+            if let Some(counter) = counter {
+                info["counter"] = counter.into();
+            }
+            json!({
+                "type": kind,
+                "uuid": uuid(),
+                "name": otp.account,
+                "issuer": otp.issuer.clone().unwrap_or_default(),
+                "note": "",
+                "favorite": false,
+                "icon": null,
+                "info": info,
+            })
+        })
+        .collect::<Vec<Value>>();
+    let vault = json!({
+        "version": 1,
+        "header": { "slots": null, "params": null },
+        "db": { "version": 2, "entries": entries },
+    });
+    serde_json::to_string_pretty(&vault).unwrap_exit("Failed to serialize JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migration_skips_unsupported_digits() {
+        let six = Otp::parse("otpauth://totp/Mail:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        let seven = Otp::parse("otpauth://totp/Bank:bob?secret=JBSWY3DPEHPK3PXP&digits=7").unwrap();
+        let (uri, skipped) = to_migration(&[six.clone(), seven]);
+        assert_eq!(skipped, 1);
+        let (otps, skipped) = parse_migration(&uri).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(otps.len(), 1);
+        assert_eq!(otps[0].to_uri(), six.to_uri());
+    }
+
+    #[test]
+    fn huge_lengths_are_truncated_data() {
+        let mut data = vec![0x0a];
+        data.extend([0xff; 9]);
+        data.push(0x01);
+        let err = Reader::new(&data).field().err();
+        assert_eq!(err.as_deref(), Some("Migration data truncated"));
+    }
+
+    #[test]
+    fn json_digits_are_kept_for_validation() {
+        let text = r#"[{"type": "TOTP", "label": "Mail:alice", "secret": "JBSWY3DPEHPK3PXP", "digits": 10, "period": 30}]"#;
+        let otps = parse_json(text).unwrap();
+        assert!(Otp::parse(&otps[0].to_uri()).is_err());
+    }
+}
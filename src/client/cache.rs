@@ -0,0 +1,35 @@
+use crate::{Deserialize, EncryptedPassword, Serialize};
+use home_config::HomeConfig;
+use std::io::Result;
+
+// Last successful `GET /passwords` response, the data is still encrypted
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HttpCache {
+    pub server: String,
+    pub user: String,
+    pub esalt: String,
+    pub iterations: u32,
+    pub passwords: Vec<EncryptedPassword>,
+    pub synced: u64,
+}
+
+impl HttpCache {
+    fn home_config() -> HomeConfig {
+        HomeConfig::new(env!("CARGO_PKG_NAME"), "cache.json")
+    }
+
+    pub fn read(server: &str, user: &str) -> Option<Self> {
+        Self::home_config()
+            .json::<Self>()
+            .ok()
+            .filter(|cache| cache.server == server && cache.user == user)
+    }
+
+    pub fn save(&self) {
+        let _ = Self::home_config().save_json(self);
+    }
+
+    pub fn delete() -> Result<()> {
+        Self::home_config().delete()
+    }
+}
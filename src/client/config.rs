@@ -1,4 +1,7 @@
-use crate::{absolute_path, trim_str, Deserialize, Serialize, ThrowError, Zeroize, ZeroizeOnDrop};
+use crate::{
+    absolute_path, trim_str, Deserialize, Kdf, Serialize, ThrowError, Zeroize, ZeroizeOnDrop,
+    ARGON2_MAX_MEMORY, ARGON2_MAX_PARALLELISM, ARGON2_MAX_TIME, ITERATIONS, PBKDF2_MAX_ITERATIONS,
+};
 use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
 use reqwest::Url;
 
@@ -20,10 +23,121 @@ pub struct HttpConfig {
 #[derive(Debug, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct LibraryConfig {
     pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<String>,
     #[serde(skip)]
     pub password: String,
 }
 
+impl LibraryConfig {
+    pub fn read(path: String, keyfile: Option<String>) -> Self {
+        let password = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Password ({})", trim_str(&path)))
+            .interact()
+            .unwrap_exit("Read password");
+        Self {
+            path,
+            keyfile,
+            password,
+        }
+    }
+
+    pub fn read_new(path: String, keyfile: Option<String>) -> Self {
+        let password = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("New password ({})", trim_str(&path)))
+            .with_confirmation("Repeat password", "Passwords do not match")
+            .interact()
+            .unwrap_exit("Read password");
+        Self {
+            path,
+            keyfile,
+            password,
+        }
+    }
+
+    // Empty input means no keyfile
+    pub fn read_keyfile_path(initial: Option<&str>) -> Option<String> {
+        let path: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Keyfile path (optional)")
+            .with_initial_text(initial.unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()
+            .unwrap_exit("Read keyfile path");
+        Some(path).filter(|p| !p.is_empty()).map(absolute_path)
+    }
+
+    pub fn read_keyfile(&self) -> Option<Vec<u8>> {
+        self.keyfile
+            .as_ref()
+            .map(|path| std::fs::read(path).unwrap_exit(format!("Read keyfile failed {}", path)))
+    }
+
+    fn read_cost(prompt: &str, default: u32, min: u32, max: u32) -> u32 {
+        Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(default.clamp(min, max))
+            .validate_with(|input: &u32| -> Result<(), String> {
+                match *input {
+                    n if n < min => Err(format!("Must be at least {}", min)),
+                    n if n > max => Err(format!("Must be at most {}", max)),
+                    _ => Ok(()),
+                }
+            })
+            .interact()
+            .unwrap_exit("Read KDF parameters")
+    }
+
+    // Read the parameters of the same KDF, using the current ones as default
+    pub fn read_kdf(default: Kdf) -> Kdf {
+        match default {
+            Kdf::Pbkdf2 { iterations } => Kdf::Pbkdf2 {
+                iterations: Self::read_cost(
+                    "PBKDF2 iterations",
+                    iterations,
+                    1,
+                    PBKDF2_MAX_ITERATIONS,
+                ),
+            },
+            Kdf::Argon2id {
+                memory,
+                time,
+                parallelism,
+            } => {
+                let memory = Self::read_cost("Argon2id memory (KiB)", memory, 8, ARGON2_MAX_MEMORY);
+                let time = Self::read_cost("Argon2id iterations", time, 1, ARGON2_MAX_TIME);
+                // Each lane needs at least 8 KiB
+                let parallelism = Self::read_cost(
+                    "Argon2id parallelism",
+                    parallelism,
+                    1,
+                    ARGON2_MAX_PARALLELISM.min(memory / 8),
+                );
+                Kdf::Argon2id {
+                    memory,
+                    time,
+                    parallelism,
+                }
+            }
+        }
+    }
+
+    pub fn select_kdf() -> Kdf {
+        let types = vec!["Argon2id (recommended)", "PBKDF2-HMAC-SHA256"];
+        let selected = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Key derivation function")
+            .items(&types)
+            .default(0)
+            .interact()
+            .unwrap_exit("Read KDF");
+        match selected {
+            0 => Self::read_kdf(Kdf::default()),
+            _ => Self::read_kdf(Kdf::Pbkdf2 {
+                iterations: ITERATIONS,
+            }),
+        }
+    }
+}
+
 impl Config {
     pub fn from_stdin() -> Config {
         let types = vec!["Use Kwrap Server", "Use Kwrap Library"];
@@ -41,6 +155,7 @@ impl Config {
             }),
             1 => Self::Library(LibraryConfig {
                 path: Self::read_path(),
+                keyfile: LibraryConfig::read_keyfile_path(None),
                 password: String::new(),
             }),
             _ => unimplemented!(),
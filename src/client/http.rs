@@ -15,12 +15,43 @@ struct ESalt {
     esalt: String,
 }
 
-#[derive(Debug, Deserialize, Zeroize, ZeroizeOnDrop)]
-struct EncryptedPassword {
-    // pid: String,
+#[derive(Debug, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(from = "StoredPassword")]
+pub struct EncryptedPassword {
+    pid: String,
     data: String,
 }
 
+// Offline caches written before the pid was kept only hold the data
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredPassword {
+    Full { pid: String, data: String },
+    Data(String),
+}
+
+impl From<StoredPassword> for EncryptedPassword {
+    fn from(item: StoredPassword) -> Self {
+        match item {
+            StoredPassword::Full { pid, data } => Self { pid, data },
+            StoredPassword::Data(data) => Self {
+                pid: String::new(),
+                data,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Zeroize, ZeroizeOnDrop)]
+struct EncryptedData {
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pid {
+    pid: String,
+}
+
 #[derive(Debug, Default, Zeroize, ZeroizeOnDrop)]
 struct Auth {
     user: String,
@@ -33,6 +64,8 @@ pub struct HttpClient {
     config: HttpConfig,
     auth: Auth,
     key: [u8; 32],
+    cache: HttpCache,
+    offline: bool,
 }
 
 impl Drop for HttpClient {
@@ -57,17 +90,43 @@ impl HttpClient {
             config,
             auth: Auth::default(),
             key: [0; 32],
+            cache: HttpCache::default(),
+            offline: false,
         }
     }
 
-    fn to_data<T: DeserializeOwned>(rst: Result<Response>) -> T {
-        let res = rst
-            .map_err(|err| err.to_string())
-            .unwrap_exit("HTTP Request");
+    // Returns `None` if the server is unreachable
+    fn check(rst: Result<Response>) -> std::result::Result<Option<Response>, String> {
+        let res = match rst {
+            Ok(res) => res,
+            Err(err) if err.is_connect() || err.is_timeout() => return Ok(None),
+            Err(err) => return Err(format!("HTTP Request\n{:#?}", err.to_string())),
+        };
+        if res.status().is_server_error() {
+            return Ok(None);
+        }
         if res.status() != StatusCode::OK {
-            exit!("{}\nBody: {}", res.status(), res.text().unwrap_or_default());
+            let status = res.status();
+            return Err(format!(
+                "{}\nBody: {}",
+                status,
+                res.text().unwrap_or_default()
+            ));
         }
-        res.json::<T>().unwrap_exit("Failed to parse response")
+        Ok(Some(res))
+    }
+
+    fn to_response(rst: Result<Response>) -> Option<Response> {
+        Self::check(rst).unwrap_or_else(|err| exit!("{}", err))
+    }
+
+    fn to_data<T: DeserializeOwned>(rst: Result<Response>) -> Option<T> {
+        Self::to_response(rst).map(|res| res.json::<T>().unwrap_exit("Failed to parse response"))
+    }
+
+    // Changes need the server, failures are returned to the caller
+    fn to_online(rst: Result<Response>) -> std::result::Result<Response, String> {
+        Self::check(rst)?.ok_or_else(|| "Server unreachable".to_string())
     }
 
     pub fn login(&mut self) {
@@ -76,7 +135,10 @@ impl HttpClient {
             .client
             .get(format!("{}/user/prelogin/{}", self.config.server, user))
             .send();
-        let prelogin = Self::to_data::<PreloginData>(rst);
+        let prelogin = match Self::to_data::<PreloginData>(rst) {
+            Some(data) => data,
+            None => return self.login_offline(user),
+        };
         let auth = Auth {
             user,
             password: base64::encode(pbkdf2(
@@ -91,29 +153,140 @@ impl HttpClient {
             .basic_auth(&auth.user, Some(&auth.password))
             .send();
 
-        let esalt = Self::to_data::<ESalt>(rst).esalt;
+        let esalt = match Self::to_data::<ESalt>(rst) {
+            Some(data) => data.esalt,
+            None => return self.login_offline(auth.user.clone()),
+        };
         self.key = pbkdf2(
             &self.config.password,
-            decode_base64(esalt),
+            decode_base64(&esalt),
             prelogin.iterations,
         );
+        self.cache = HttpCache {
+            server: self.config.server.clone(),
+            user: auth.user.clone(),
+            esalt,
+            iterations: prelogin.iterations,
+            ..Default::default()
+        };
         self.auth = auth;
     }
 
+    fn login_offline(&mut self, user: String) {
+        let cache = HttpCache::read(&self.config.server, &user)
+            .unwrap_or_else(|| exit!("Server unreachable and no offline cache"));
+        self.key = pbkdf2(
+            &self.config.password,
+            decode_base64(&cache.esalt),
+            cache.iterations,
+        );
+        self.cache = cache;
+        self.offline = true;
+    }
+
+    pub fn id(&self) -> String {
+        format!("{} {}", self.config.server, self.config.user)
+    }
+
+    pub fn subkey(&self, info: &str) -> [u8; 32] {
+        subkey(&self.key, info)
+    }
+
+    // Time of the last successful sync when using the offline cache
+    pub fn offline(&self) -> Option<u64> {
+        self.offline.then_some(self.cache.synced)
+    }
+
     // The login fn must be called first
-    pub fn passwords(self) -> Vec<PasswordData> {
+    pub fn passwords(&mut self) -> Vec<PasswordData> {
+        if !self.offline {
+            let rst = self
+                .client
+                .get(format!("{}/passwords", self.config.server))
+                .basic_auth(&self.auth.user, Some(&self.auth.password))
+                .send();
+            match Self::to_data::<Vec<EncryptedPassword>>(rst) {
+                Some(list) => {
+                    self.cache.passwords = list;
+                    self.cache.synced = timestamp();
+                    self.cache.save();
+                }
+                None => {
+                    let (server, user) = (&self.config.server, &self.cache.user);
+                    self.cache = HttpCache::read(server, user)
+                        .unwrap_or_else(|| exit!("Server unreachable and no offline cache"));
+                    self.offline = true;
+                }
+            }
+        }
         let cipher = Aes256Gcm::new(self.key);
-        let rst = self
-            .client
-            .get(format!("{}/passwords", self.config.server))
-            .basic_auth(&self.auth.user, Some(&self.auth.password))
-            .send();
-        Self::to_data::<Vec<EncryptedPassword>>(rst)
-            .into_iter()
+        self.cache
+            .passwords
+            .iter()
             .map(|item| {
                 let mut data = decode_base64(&item.data);
-                cipher.decrypt_to(&mut data)
+                let mut password = cipher.decrypt_to::<PasswordData>(&mut data, &[]);
+                password.pid = Some(item.pid.clone()).filter(|pid| !pid.is_empty());
+                password
             })
             .collect()
     }
+
+    // The pid is the id of the server blob, it is not stored inside it
+    fn encrypt(&self, data: &PasswordData) -> EncryptedData {
+        let cipher = Aes256Gcm::new(self.key);
+        let mut data = data.clone();
+        data.pid = None;
+        EncryptedData {
+            data: base64::encode(cipher.encrypt_from(&data, &[])),
+        }
+    }
+
+    // Upload a new password and return its pid
+    pub fn create(&self, data: &PasswordData) -> std::result::Result<String, String> {
+        let rst = self
+            .client
+            .post(format!("{}/passwords", self.config.server))
+            .basic_auth(&self.auth.user, Some(&self.auth.password))
+            .json(&self.encrypt(data))
+            .send();
+        Self::to_online(rst)?
+            .json::<Pid>()
+            .map(|data| data.pid)
+            .map_err(|err| format!("Failed to parse response\n{}", err))
+    }
+
+    pub fn update(&self, pid: &str, data: &PasswordData) -> std::result::Result<(), String> {
+        let rst = self
+            .client
+            .put(format!("{}/passwords/{}", self.config.server, pid))
+            .basic_auth(&self.auth.user, Some(&self.auth.password))
+            .json(&self.encrypt(data))
+            .send();
+        Self::to_online(rst).map(|_| ())
+    }
+
+    pub fn delete(&self, pid: &str) -> std::result::Result<(), String> {
+        let rst = self
+            .client
+            .delete(format!("{}/passwords/{}", self.config.server, pid))
+            .basic_auth(&self.auth.user, Some(&self.auth.password))
+            .send();
+        Self::to_online(rst).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_caches_are_read() {
+        let json = r#"{"server": "s", "user": "u", "esalt": "", "iterations": 1,
+            "passwords": ["b2xk", {"pid": "1", "data": "bmV3"}], "synced": 2}"#;
+        let cache = serde_json::from_str::<HttpCache>(json).unwrap();
+        assert_eq!(cache.passwords[0].pid, "");
+        assert_eq!(cache.passwords[0].data, "b2xk");
+        assert_eq!(cache.passwords[1].pid, "1");
+    }
 }
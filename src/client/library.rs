@@ -1,17 +1,154 @@
 use crate::*;
 use std::fs::File;
 
+// Default PBKDF2 iterations for newly created files
+pub const ITERATIONS: u32 = 100_000;
+
+pub fn new_uid() -> String {
+    hex::encode(random_bytes::<16>())
+}
+
 pub struct LibraryClient {
     pub passwords: Vec<PasswordData>,
+    path: String,
+    // The header of the file, `data` is only set when saving
+    kwrap: KwrapFile,
+    key: [u8; 32],
+}
+
+impl Drop for LibraryClient {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
 }
 
 impl LibraryClient {
     pub fn new(config: &LibraryConfig) -> Self {
+        Self::open(config).unwrap_or_else(|| exit!("Password error"))
+    }
+
+    // Returns `None` if the password or keyfile is wrong
+    pub fn open(config: &LibraryConfig) -> Option<Self> {
         let f = File::open(&config.path).unwrap_exit(format!("Open file failed {}", config.path));
         let mut kwrap = KwrapFile::parse(f).unwrap_exit("Parse Kwrap file failed");
-        let key = pbkdf2(&config.password, kwrap.salt, kwrap.iterations);
-        Self {
-            passwords: Aes256Gcm::new(key).decrypt_to(&mut kwrap.data),
+        if kwrap.keyfile() && config.keyfile.is_none() {
+            exit!(
+                "{} requires a keyfile, pass it with --keyfile <path>",
+                config.path
+            );
         }
+        let mut data = std::mem::take(&mut kwrap.data);
+        let mut client = Self {
+            passwords: vec![],
+            path: config.path.clone(),
+            kwrap,
+            key: [0; 32],
+        };
+        client.derive_key(config);
+        let aad = client.kwrap.aad().unwrap_exit("Parse Kwrap file failed");
+        let cipher = Aes256Gcm::new(client.key);
+        let json = cipher.try_decrypt(&mut data, &aad).ok()?;
+        client.passwords = serde_json::from_slice(json).unwrap_exit("Failed to parse JSON");
+        json.zeroize();
+        Some(client)
+    }
+
+    // Create an empty library, it is written on the first `save`
+    pub fn create(config: &LibraryConfig, kdf: Kdf) -> Self {
+        let mut client = Self {
+            passwords: vec![],
+            path: config.path.clone(),
+            kwrap: KwrapFile {
+                version: LATEST_VERSION,
+                flags: 0,
+                kdf,
+                salt: [0; 32],
+                data: vec![],
+            },
+            key: [0; 32],
+        };
+        client.rekey(config, kdf);
+        client
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn subkey(&self, info: &str) -> [u8; 32] {
+        subkey(&self.key, info)
+    }
+
+    fn derive_key(&mut self, config: &LibraryConfig) {
+        let mut keyfile = config.read_keyfile();
+        self.key = self.kwrap.key(&config.password, keyfile.as_deref());
+        keyfile.zeroize();
+    }
+
+    pub fn version(&self) -> u8 {
+        self.kwrap.version
+    }
+
+    pub fn kdf(&self) -> Kdf {
+        self.kwrap.kdf
+    }
+
+    // Use a new password, keyfile, salt and KDF for the next `save`
+    pub fn rekey(&mut self, config: &LibraryConfig, kdf: Kdf) {
+        if self.kwrap.version == VERSION_1 && !matches!(kdf, Kdf::Pbkdf2 { .. }) {
+            exit!("Kwrap version 1 only supports PBKDF2, use `kwrap upgrade`");
+        }
+        if self.kwrap.version == VERSION_1 && config.keyfile.is_some() {
+            exit!("Kwrap version 1 does not support keyfiles, use `kwrap upgrade`");
+        }
+        self.kwrap.flags = match config.keyfile {
+            Some(_) => FLAG_KEYFILE,
+            None => 0,
+        };
+        self.kwrap.salt = random_bytes();
+        self.kwrap.kdf = kdf;
+        self.derive_key(config);
+    }
+
+    // Convert to the latest file format on the next `save`
+    pub fn upgrade(&mut self, config: &LibraryConfig, kdf: Kdf) {
+        self.kwrap.version = LATEST_VERSION;
+        self.rekey(config, kdf);
+    }
+
+    fn encode(&mut self) -> Result<Vec<u8>, String> {
+        // Entries without a server id get a `uid` when the file is written
+        for data in &mut self.passwords {
+            if data.pid.is_none() && data.uid.is_none() {
+                data.uid = Some(new_uid());
+            }
+        }
+        let aad = self.kwrap.aad().map_err(|err| err.to_string())?;
+        self.kwrap.data = Aes256Gcm::new(self.key).encrypt_from(&self.passwords, &aad);
+        let bytes = self.kwrap.to_vec().map_err(|err| err.to_string());
+        self.kwrap.data = vec![];
+        bytes
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.write(true)
+    }
+
+    // Save again without replacing the backup of the first `save`
+    pub fn resave(&mut self) -> Result<(), String> {
+        self.write(false)
+    }
+
+    fn write(&mut self, backup: bool) -> Result<(), String> {
+        let bytes = self.encode()?;
+        write_atomic(&self.path, &bytes, backup)
+            .map_err(|err| format!("Write file failed {}\n{}", self.path, err))
+    }
+
+    // Write to another path with the same key, no backup is kept
+    pub fn save_as(&mut self, path: &str) -> Result<(), String> {
+        let bytes = self.encode()?;
+        write_atomic(path, &bytes, false)
+            .map_err(|err| format!("Write file failed {}\n{}", path, err))
     }
 }
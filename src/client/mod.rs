@@ -1,16 +1,31 @@
+mod cache;
 mod config;
 mod http;
 mod library;
+mod vault;
 
-use crate::{timestamp, Deserialize, Zeroize, ZeroizeOnDrop};
+use crate::otp::{self, Otp, OtpKind};
+use crate::{timestamp, timestamp_f64, Deserialize, Serialize, Zeroize, ZeroizeOnDrop};
+pub use cache::HttpCache;
 pub use config::*;
-pub use http::HttpClient;
-pub use library::LibraryClient;
+pub use http::{EncryptedPassword, HttpClient};
+pub use library::{new_uid, LibraryClient, ITERATIONS};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
 use time_humanize::HumanTime;
-use totp_rs::TOTP;
+pub use vault::Vault;
 
-#[derive(Debug, Default, Deserialize, Clone, Zeroize, ZeroizeOnDrop)]
+// Maximum number of previous passwords kept per entry
+pub const HISTORY_LIMIT: usize = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Zeroize, ZeroizeOnDrop)]
 pub struct PasswordData {
+    // Server id, kept in library files that are synced with a server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<String>,
+    // Random id of a library entry, it stays the same when the entry is renamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
     pub pin: Option<u32>,
     pub icon: Option<String>,
     pub name: Option<String>,
@@ -25,19 +40,57 @@ pub struct PasswordData {
     pub tags: Option<Vec<String>>,
     pub updated: Option<u32>,
     pub archive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<PasswordHistory>>,
+    // Position in the vault, only set by `Vault::passwords`
+    #[serde(skip)]
+    pub index: Option<usize>,
+    // Unknown fields are kept so that saving does not lose data
+    #[serde(flatten)]
+    #[zeroize(skip)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Zeroize, ZeroizeOnDrop)]
 pub struct CustomField {
     pub name: String,
     pub value: String,
     pub hidden: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Zeroize, ZeroizeOnDrop)]
+pub struct PasswordHistory {
+    pub password: String,
+    pub time: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DisplayAction {
+    Copy,
+    ToggleHistory,
+    // Generate the next HOTP code and save the counter
+    Hotp,
+    // Open the notes viewer
+    Notes,
+}
+
+// Countdown of a time-based code
+pub struct OtpTimer {
+    pub remaining: f64,
+    pub step: u64,
+    pub next: String,
+}
+
 pub struct DisplayValue {
     pub key: String,
     pub value: String,
     pub copy_value: String,
+    pub action: DisplayAction,
+    pub timer: Option<OtpTimer>,
+    // Shown as `******` unless revealed
+    pub hidden: bool,
+    // Can be opened in the browser
+    pub link: bool,
 }
 
 impl DisplayValue {
@@ -46,6 +99,17 @@ impl DisplayValue {
             key: key.to_string(),
             value: value.to_string(),
             copy_value: copy_value.to_string(),
+            action: DisplayAction::Copy,
+            timer: None,
+            hidden: false,
+            link: false,
+        }
+    }
+
+    fn hidden<K: ToString, C: ToString>(key: K, copy_value: C) -> Self {
+        Self {
+            hidden: true,
+            ..Self::new(key, "******", copy_value)
         }
     }
 }
@@ -72,7 +136,62 @@ impl PasswordData {
             .unwrap_or_default()
     }
 
-    pub fn to_display_value(&self) -> Vec<DisplayValue> {
+    // The previous password is kept in the history
+    pub fn set_password(&mut self, password: String) {
+        if let Some(old) = self.password.replace(password) {
+            let history = self.history.get_or_insert_with(Vec::new);
+            history.insert(
+                0,
+                PasswordHistory {
+                    password: old,
+                    time: timestamp(),
+                },
+            );
+            history.truncate(HISTORY_LIMIT);
+        }
+        self.updated = Some(timestamp() as u32);
+    }
+
+    // Tags in `from` become `to`, returns whether anything changed
+    pub fn rename_tags(&mut self, from: &[String], to: &str) -> bool {
+        let Some(tags) = &mut self.tags else {
+            return false;
+        };
+        if !tags.iter().any(|tag| from.contains(tag)) {
+            return false;
+        }
+        let mut renamed = vec![];
+        for tag in tags.drain(..) {
+            let tag = if from.contains(&tag) {
+                to.to_string()
+            } else {
+                tag
+            };
+            if !renamed.contains(&tag) {
+                renamed.push(tag);
+            }
+        }
+        *tags = renamed;
+        self.updated = Some(timestamp() as u32);
+        true
+    }
+
+    // HOTP code for the current counter, the counter is advanced
+    pub fn next_hotp(&mut self) -> Result<String, String> {
+        let uri = self.otp.as_deref().unwrap_or_default();
+        let otp = Otp::parse(uri)?;
+        let OtpKind::Hotp { counter } = otp.kind else {
+            return Err("Not a HOTP entry".to_string());
+        };
+        let next = counter
+            .checked_add(1)
+            .ok_or_else(|| "HOTP counter overflow".to_string())?;
+        self.otp = Some(otp::set_counter(uri, next)?);
+        self.updated = Some(timestamp() as u32);
+        Ok(otp.generate(counter))
+    }
+
+    pub fn to_display_value(&self, show_history: bool) -> Vec<DisplayValue> {
         let mut values = vec![];
         if let Some(value) = &self.user {
             values.push(DisplayValue::new("User", value, value));
@@ -84,31 +203,86 @@ impl PasswordData {
             values.push(DisplayValue::new("Phone", value, value));
         }
         if let Some(value) = &self.password {
-            values.push(DisplayValue::new("Password", "******", value));
+            values.push(DisplayValue::hidden("Password", value));
         }
-        if let Some(otp) = &self.otp {
-            match TOTP::<Vec<u8>>::from_url(otp) {
-                Ok(totp) => {
-                    let t = timestamp();
-                    let token = totp.generate(t);
-                    values.push(DisplayValue::new(
-                        format!("One-time password ({}s)", totp.step - t % totp.step),
-                        &token,
-                        &token,
+        if let Some(history) = self.history.as_ref().filter(|h| !h.is_empty()) {
+            let arrow = if show_history { "▾" } else { "▸" };
+            values.push(DisplayValue {
+                action: DisplayAction::ToggleHistory,
+                ..DisplayValue::new(
+                    format!("Password history ({})", history.len()),
+                    format!("{} Press Enter to toggle", arrow),
+                    "",
+                )
+            });
+            if show_history {
+                for item in history {
+                    values.push(DisplayValue::hidden(
+                        format!(
+                            "  Changed {}",
+                            HumanTime::from_duration_since_timestamp(item.time)
+                        ),
+                        &item.password,
                     ));
                 }
-                Err(_) => {
-                    values.push(DisplayValue::new("One-time password", "------", ""));
+            }
+        }
+        if let Some(uri) = &self.otp {
+            match Otp::parse(uri) {
+                Ok(otp) => match (otp.kind, otp.step()) {
+                    (OtpKind::Hotp { counter }, _) => values.push(DisplayValue {
+                        action: DisplayAction::Hotp,
+                        ..DisplayValue::new(
+                            format!("One-time password (counter {})", counter),
+                            "Press Enter to generate and copy",
+                            "",
+                        )
+                    }),
+                    (_, step) => {
+                        let t = timestamp_f64();
+                        let step = step.unwrap_or(30);
+                        let token = otp.generate_at(t as u64);
+                        values.push(DisplayValue {
+                            timer: Some(OtpTimer {
+                                remaining: step as f64 - t % step as f64,
+                                step,
+                                next: otp.generate_at(t as u64 + step),
+                            }),
+                            ..DisplayValue::new("One-time password", &token, &token)
+                        });
+                    }
+                },
+                Err(err) => {
+                    values.push(DisplayValue::new(
+                        "One-time password",
+                        format!("Invalid: {}", err),
+                        "",
+                    ));
                 }
             }
         }
         if let Some(links) = &self.links {
             for value in links {
-                values.push(DisplayValue::new("Link", value, value));
+                values.push(DisplayValue {
+                    link: true,
+                    ..DisplayValue::new("Link", value, value)
+                });
             }
         }
         if let Some(value) = &self.notes {
-            values.push(DisplayValue::new("Notes", value, value));
+            // The first line, the rest is read in the notes viewer
+            let mut lines = value.lines();
+            let first = lines.next().unwrap_or_default();
+            let more = lines.count();
+            let shown = match more {
+                0 => first.to_string(),
+                1 => format!("{} (+1 line, press Enter to read)", first),
+                n => format!("{} (+{} lines, press Enter to read)", first, n),
+            };
+            values.push(DisplayValue {
+                action: DisplayAction::Notes,
+                ..DisplayValue::new("Notes", shown, value)
+            });
         }
         if let Some(custom) = &self.custom {
             for CustomField {
@@ -118,7 +292,7 @@ impl PasswordData {
             } in custom
             {
                 if *hidden {
-                    values.push(DisplayValue::new(name, "******", value));
+                    values.push(DisplayValue::hidden(name, value));
                 } else {
                     values.push(DisplayValue::new(name, value, value));
                 }
@@ -138,3 +312,18 @@ impl PasswordData {
         values
     }
 }
+
+// Tags sorted case-insensitively, with the number of entries of each
+pub fn tag_counts<'a, I: IntoIterator<Item = &'a PasswordData>>(
+    passwords: I,
+) -> Vec<(String, usize)> {
+    let mut counts = BTreeMap::<String, usize>::new();
+    for item in passwords {
+        for tag in item.tags.iter().flatten().collect::<BTreeSet<&String>>() {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    let mut tags = counts.into_iter().collect::<Vec<(String, usize)>>();
+    tags.sort_by_cached_key(|(tag, _)| (tag.to_lowercase(), tag.clone()));
+    tags
+}
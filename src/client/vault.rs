@@ -0,0 +1,104 @@
+use crate::*;
+
+// The active backend, entries are saved one at a time
+pub enum Vault {
+    Http(HttpClient),
+    Library(LibraryClient),
+}
+
+impl Vault {
+    pub fn open(config: &Config) -> Self {
+        match config {
+            Config::Http(c) => {
+                let mut client = HttpClient::new(c.clone());
+                client.login();
+                Self::Http(client)
+            }
+            Config::Library(c) => Self::Library(LibraryClient::new(c)),
+        }
+    }
+
+    // Entries are numbered so that they can be saved back
+    pub fn passwords(&mut self) -> Vec<PasswordData> {
+        let mut passwords = match self {
+            Self::Http(client) => client.passwords(),
+            Self::Library(client) => client.passwords.clone(),
+        };
+        for (i, item) in passwords.iter_mut().enumerate() {
+            item.index = Some(i);
+        }
+        passwords
+    }
+
+    // Server and user, or the library file
+    pub fn id(&self) -> String {
+        match self {
+            Self::Http(client) => client.id(),
+            Self::Library(client) => client.path().to_string(),
+        }
+    }
+
+    pub fn subkey(&self, info: &str) -> [u8; 32] {
+        match self {
+            Self::Http(client) => client.subkey(info),
+            Self::Library(client) => client.subkey(info),
+        }
+    }
+
+    // Time of the last sync when using the offline cache
+    pub fn offline(&self) -> Option<u64> {
+        match self {
+            Self::Http(client) => client.offline(),
+            Self::Library(_) => None,
+        }
+    }
+
+    // Create the entry if it has no index
+    pub fn save(&mut self, data: &mut PasswordData) -> Result<(), String> {
+        self.save_all(std::slice::from_mut(data))
+    }
+
+    // A library file is written once for all entries
+    pub fn save_all(&mut self, items: &mut [PasswordData]) -> Result<(), String> {
+        match self {
+            Self::Http(client) => {
+                if client.offline().is_some() {
+                    return Err("Offline, the cache is read-only".to_string());
+                }
+                for data in items {
+                    match &data.pid {
+                        Some(pid) => client.update(pid, data)?,
+                        None => data.pid = Some(client.create(data)?),
+                    }
+                }
+            }
+            Self::Library(client) => {
+                // Restored if the file cannot be written, so that saving can be retried
+                let passwords = client.passwords.clone();
+                let indexes = items.iter().map(|data| data.index).collect::<Vec<_>>();
+                for data in items.iter_mut() {
+                    if data.pid.is_none() && data.uid.is_none() {
+                        data.uid = Some(new_uid());
+                    }
+                    let mut item = data.clone();
+                    item.index = None;
+                    match data.index {
+                        Some(i) => client.passwords[i] = item,
+                        None => {
+                            data.index = Some(client.passwords.len());
+                            client.passwords.push(item);
+                        }
+                    }
+                }
+                if let Err(err) = client.save() {
+                    client.passwords = passwords;
+                    for (data, index) in items.iter_mut().zip(indexes) {
+                        data.index = index;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,700 @@
+use crate::authenticator;
+use crate::otp::{self, Otp, OtpKind};
+use crate::*;
+use ace::App;
+use dialoguer::{theme::ColorfulTheme, Confirm, Password, Select};
+use home_config::HomeConfig;
+use std::collections::BTreeSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// First value after the command or option
+pub fn value(app: &App, cmd: &str) -> Option<String> {
+    app.value(cmd)
+        .and_then(|values| values.first().map(|s| s.to_string()))
+}
+
+// Library path from the command line, or the logged in library
+pub fn library_path(app: &App, cmd: &str, hc: &HomeConfig) -> String {
+    if let Some(path) = value(app, cmd) {
+        return absolute_path(path);
+    }
+    match hc.json::<Config>() {
+        Ok(config) => match &config {
+            Config::Library(c) => c.path.clone(),
+            Config::Http(_) => exit!("Missing library file path"),
+        },
+        Err(_) => exit!("Missing library file path"),
+    }
+}
+
+// Keyfile from `--keyfile`, or the one saved for the logged in library
+pub fn keyfile_path(app: &App, hc: &HomeConfig, path: &str) -> Option<String> {
+    if let Some(keyfile) = value(app, "--keyfile") {
+        return Some(absolute_path(keyfile));
+    }
+    let config = hc.json::<Config>().ok()?;
+    match &config {
+        Config::Library(c) if c.path == path => c.keyfile.clone(),
+        _ => None,
+    }
+}
+
+// Logged in server config with the password read from stdin
+pub fn http_config(hc: &HomeConfig) -> HttpConfig {
+    let mut config = hc.json::<Config>().unwrap_exit("Read config file");
+    if !matches!(config, Config::Http(_)) {
+        exit!("This command requires a Kwrap server login");
+    }
+    config.read_password();
+    match &config {
+        Config::Http(c) => c.clone(),
+        Config::Library(_) => unreachable!(),
+    }
+}
+
+pub fn server_passwords(hc: &HomeConfig) -> Vec<PasswordData> {
+    let mut client = HttpClient::new(http_config(hc));
+    client.login();
+    let passwords = client.passwords();
+    if client.offline().is_some() {
+        exit!("Server unreachable");
+    }
+    passwords
+}
+
+pub fn open_library(app: &App, cmd: &str, hc: &HomeConfig) -> (LibraryConfig, LibraryClient) {
+    open_library_path(app, hc, library_path(app, cmd, hc))
+}
+
+pub fn open_library_path(
+    app: &App,
+    hc: &HomeConfig,
+    path: String,
+) -> (LibraryConfig, LibraryClient) {
+    let keyfile = keyfile_path(app, hc, &path);
+    let config = LibraryConfig::read(path, keyfile);
+    let library = LibraryClient::new(&config);
+    (config, library)
+}
+
+// Remember the keyfile if it is the logged in library
+fn save_keyfile(hc: &HomeConfig, path: &str, keyfile: &Option<String>) {
+    if let Ok(mut config) = hc.json::<Config>() {
+        if let Config::Library(c) = &mut config {
+            if c.path == path {
+                c.keyfile = keyfile.clone();
+                let _ = hc.save_json(&config);
+            }
+        }
+    }
+}
+
+pub fn passwd(app: &App, hc: &HomeConfig) {
+    let (old, mut library) = open_library(app, "passwd", hc);
+    let keyfile = LibraryConfig::read_keyfile_path(old.keyfile.as_deref());
+    let config = LibraryConfig::read_new(old.path.clone(), keyfile);
+    let kdf = LibraryConfig::read_kdf(library.kdf());
+    library.rekey(&config, kdf);
+    library.save().unwrap_or_else(|err| exit!("{}", err));
+    save_keyfile(hc, &config.path, &config.keyfile);
+    println!(
+        "Password changed, the old file is kept as {}.bak",
+        config.path
+    );
+}
+
+pub fn upgrade(app: &App, hc: &HomeConfig) {
+    let (config, mut library) = open_library(app, "upgrade", hc);
+    if library.version() == LATEST_VERSION {
+        exit!("Already the latest version {}", LATEST_VERSION);
+    }
+    let kdf = LibraryConfig::select_kdf();
+    library.upgrade(&config, kdf);
+    library.save().unwrap_or_else(|err| exit!("{}", err));
+    println!(
+        "Upgraded to version {}, the old file is kept as {}.bak",
+        LATEST_VERSION, config.path
+    );
+}
+
+pub fn keyfile(app: &App) {
+    let values = app.value("keyfile").unwrap_or_default();
+    let path = match values.as_slice() {
+        [cmd, path] if cmd.as_str() == "generate" => absolute_path(path.to_string()),
+        _ => exit!("Usage: kwrap keyfile generate <path>"),
+    };
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut f = options
+        .open(&path)
+        .unwrap_exit(format!("Create keyfile failed {}", path));
+    let mut key = random_bytes::<64>();
+    f.write_all(&key)
+        .and_then(|_| f.sync_all())
+        .unwrap_exit(format!("Write keyfile failed {}", path));
+    key.zeroize();
+    println!("Keyfile created {}", path);
+    println!("Use it with `kwrap passwd [file]`, a lost keyfile cannot be recovered");
+}
+
+// Trailing bytes that `inspect --verify` tries to strip, one decryption per length
+const TRAILING_SCAN: usize = 64;
+
+fn row<V: std::fmt::Display>(name: &str, value: V) {
+    println!("{:<10} {}", format!("{}:", name), value);
+}
+
+pub fn inspect(app: &App, hc: &HomeConfig) {
+    let path = value(app, "inspect")
+        .map(absolute_path)
+        .unwrap_or_else(|| exit!("Missing library file path"));
+    let bytes = std::fs::read(&path).unwrap_exit(format!("Open file failed {}", path));
+    row("File", format!("{} ({} bytes)", path, bytes.len()));
+    let kwrap = KwrapFile::parse(bytes.as_slice()).unwrap_or_else(|err| exit!("{}", err));
+
+    let header = kwrap.header().unwrap_or_else(|err| exit!("{}", err));
+    let flags = match kwrap.keyfile() {
+        true => "keyfile",
+        false => "none",
+    };
+    let authenticated = match kwrap.version {
+        VERSION_1 => "",
+        _ => ", authenticated",
+    };
+    row("Magic", format!("{} (ok)", hex::encode(&header[..6])));
+    row(
+        "Version",
+        format!("{} (latest {})", kwrap.version, LATEST_VERSION),
+    );
+    row("Flags", flags);
+    row("KDF", kwrap.kdf);
+    row("Salt", hex::encode(kwrap.salt));
+    row("Header", format!("{} bytes{}", header.len(), authenticated));
+    row("Nonce", hex::encode(&kwrap.data[..12]));
+    row(
+        "Payload",
+        format!(
+            "{} bytes (ciphertext {}, tag 16)",
+            kwrap.data.len(),
+            kwrap.data.len() - 12 - 16
+        ),
+    );
+
+    if app.value("--verify").is_none() {
+        row("Trailing", "unknown, use --verify");
+        return;
+    }
+
+    let config = LibraryConfig::read(path.clone(), keyfile_path(app, hc, &path));
+    if kwrap.keyfile() && config.keyfile.is_none() {
+        exit!("{} requires a keyfile, pass it with --keyfile <path>", path);
+    }
+    let mut keyfile = config.read_keyfile();
+    let cipher = Aes256Gcm::new(kwrap.key(&config.password, keyfile.as_deref()));
+    keyfile.zeroize();
+    let aad = kwrap.aad().unwrap_or_else(|err| exit!("{}", err));
+    let verify = |len: usize| {
+        let mut buf = kwrap.data[..len].to_vec();
+        let ok = cipher.try_decrypt(&mut buf, &aad).is_ok();
+        buf.zeroize();
+        ok
+    };
+
+    let len = kwrap.data.len();
+    if verify(len) {
+        row("Trailing", "none");
+        row("Verify", "ok");
+        return;
+    }
+    match (1..=TRAILING_SCAN.min(len - MINIMUM_DATA)).find(|n| verify(len - n)) {
+        Some(n) => {
+            row("Trailing", format!("{} bytes of garbage", n));
+            row("Verify", format!("ok without the last {} bytes", n));
+        }
+        None => {
+            row("Trailing", "unknown");
+            row(
+                "Verify",
+                "failed, wrong password or keyfile, or corrupted data",
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn diff(app: &App, hc: &HomeConfig) {
+    let paths = app
+        .value("diff")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| absolute_path(path.to_string()))
+        .collect::<Vec<String>>();
+    let server = app.value("--server").is_some();
+    let (old, new) = match (paths.as_slice(), server) {
+        ([a, b], false) => {
+            let (_, mut a) = open_library_path(app, hc, a.clone());
+            let (_, mut b) = open_library_path(app, hc, b.clone());
+            (
+                std::mem::take(&mut a.passwords),
+                std::mem::take(&mut b.passwords),
+            )
+        }
+        ([a], true) => {
+            let (_, mut a) = open_library_path(app, hc, a.clone());
+            (std::mem::take(&mut a.passwords), server_passwords(hc))
+        }
+        _ => exit!("Usage: kwrap diff <a> <b> or kwrap diff <file> --server"),
+    };
+    diff::diff(&old, &new, app.value("--reveal").is_some());
+}
+
+// Try the password of the first file before asking again
+fn open_with_password(app: &App, hc: &HomeConfig, path: String, password: &str) -> LibraryClient {
+    let config = LibraryConfig {
+        keyfile: keyfile_path(app, hc, &path),
+        path,
+        password: password.to_string(),
+    };
+    LibraryClient::open(&config)
+        .unwrap_or_else(|| open_library_path(app, hc, config.path.clone()).1)
+}
+
+pub fn merge(app: &App, hc: &HomeConfig) {
+    let paths = app
+        .value("merge")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| absolute_path(path.to_string()))
+        .collect::<Vec<String>>();
+    let [base, local, remote] = paths.as_slice() else {
+        exit!("Usage: kwrap merge <base> <local> <remote> [-o <out>]");
+    };
+    let out = value(app, "-o")
+        .map(absolute_path)
+        .unwrap_or_else(|| local.clone());
+
+    let (config, mut local) = open_library_path(app, hc, local.clone());
+    let base = open_with_password(app, hc, base.clone(), &config.password);
+    let remote = open_with_password(app, hc, remote.clone(), &config.password);
+
+    let mut merger = merge::Merger::new(app.value("--markers").is_some());
+    local.passwords = merger.merge(&base.passwords, &local.passwords, &remote.passwords);
+    local.save_as(&out).unwrap_or_else(|err| exit!("{}", err));
+
+    println!(
+        "Merged {} entries into {}, {} conflicts",
+        local.passwords.len(),
+        out,
+        merger.conflicts
+    );
+    if merger.conflicts > 0 {
+        std::process::exit(1);
+    }
+}
+
+// Logged in vault with the password read from stdin
+pub fn open_vault(hc: &HomeConfig) -> Vault {
+    let mut config = hc.json::<Config>().unwrap_exit("Read config file");
+    config.read_password();
+    Vault::open(&config)
+}
+
+// Entry by name, case-insensitive, asks if several entries match
+pub fn find_entry(passwords: Vec<PasswordData>, name: &str) -> PasswordData {
+    let query = name.to_lowercase();
+    let mut found = passwords
+        .into_iter()
+        .filter(|item| item.archive != Some(true))
+        .filter(|item| item.name(false).to_lowercase() == query)
+        .collect::<Vec<PasswordData>>();
+    if found.is_empty() {
+        exit!("No entry named '{}'", name);
+    }
+    if found.len() == 1 {
+        return found.remove(0);
+    }
+    let items = found
+        .iter()
+        .map(|item| format!("{} ({})", item.name(false), item.user()))
+        .collect::<Vec<String>>();
+    let selected = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Several entries match")
+        .items(&items)
+        .default(0)
+        .interact()
+        .unwrap_exit("Read entry");
+    found.remove(selected)
+}
+
+pub fn rotate(app: &App, hc: &HomeConfig) {
+    let name = value(app, "rotate").unwrap_or_else(|| exit!("Usage: kwrap rotate <name>"));
+    let mut vault = open_vault(hc);
+    if vault.offline().is_some() {
+        exit!("Server unreachable");
+    }
+    let mut data = find_entry(vault.passwords(), &name);
+    let password = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("New password for '{}'", data.name(false)))
+        .with_confirmation("Repeat password", "Passwords do not match")
+        .interact()
+        .unwrap_exit("Read password");
+    data.set_password(password);
+    vault.save(&mut data).unwrap_or_else(|err| exit!("{}", err));
+    println!(
+        "Password changed, {} previous passwords kept",
+        data.history.as_ref().map(Vec::len).unwrap_or_default()
+    );
+}
+
+// Print the current code, or the seed as a QR code with `--qr`
+pub fn otp(app: &App, hc: &HomeConfig) {
+    let name = value(app, "otp").unwrap_or_else(|| exit!("Usage: kwrap otp <name> [--qr]"));
+    let mut vault = open_vault(hc);
+    let mut data = find_entry(vault.passwords(), &name);
+    let uri = data
+        .otp
+        .clone()
+        .unwrap_or_else(|| exit!("'{}' has no one-time password", data.name(false)));
+    let otp = Otp::parse(&uri).unwrap_or_else(|err| exit!("Invalid one-time password: {}", err));
+
+    if app.value("--qr").is_some() {
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Show the one-time password seed of '{}'? Anyone who can see the screen can copy it",
+                data.name(false)
+            ))
+            .default(false)
+            .interact()
+            .unwrap_exit("Read confirmation");
+        if !confirmed {
+            return;
+        }
+        let mut qr = otp::qr_code(&otp.to_uri()).unwrap_or_else(|err| exit!("{}", err));
+        println!("{}", qr);
+        qr.zeroize();
+        return;
+    }
+
+    let code = match otp.kind {
+        OtpKind::Hotp { .. } => {
+            if vault.offline().is_some() {
+                exit!("Server unreachable, the HOTP counter cannot be saved");
+            }
+            let code = data.next_hotp().unwrap_or_else(|err| exit!("{}", err));
+            vault.save(&mut data).unwrap_or_else(|err| exit!("{}", err));
+            code
+        }
+        _ => otp.generate_at(timestamp()),
+    };
+    println!("{}", code);
+    let mut usage = Usage::load(&vault);
+    usage.record(&data, "One-time password");
+    usage.save();
+}
+
+// Open the first link of an entry and copy its password
+pub fn open(app: &App, hc: &HomeConfig) {
+    let name = value(app, "open").unwrap_or_else(|| exit!("Usage: kwrap open <name>"));
+    let mut vault = open_vault(hc);
+    let data = find_entry(vault.passwords(), &name);
+    let link = data
+        .links
+        .as_ref()
+        .and_then(|links| links.first())
+        .unwrap_or_else(|| exit!("'{}' has no link", data.name(false)));
+    let url =
+        open_link(link, &Settings::read().link_schemes).unwrap_or_else(|err| exit!("{}", err));
+    println!("Opened {}", url.host_str().unwrap_or(url.scheme()));
+
+    let Some(password) = &data.password else {
+        return println!("'{}' has no password", data.name(false));
+    };
+    copy_text(password).unwrap_or_else(|err| exit!("Copy failed: {}", err));
+    println!("Copied the password of '{}'", data.name(false));
+    let mut usage = Usage::load(&vault);
+    usage.record(&data, "Password");
+    usage.save();
+}
+
+// Entries that are not archived, or the last used with --recent
+pub fn list(app: &App, hc: &HomeConfig) {
+    let mut vault = open_vault(hc);
+    let passwords = vault.passwords();
+    if app.value("--recent").is_some() {
+        let usage = Usage::load(&vault);
+        let recent = usage.recent(&passwords, usize::MAX);
+        if recent.is_empty() {
+            println!("Nothing copied yet");
+        }
+        for item in recent {
+            println!("{}  ({})", item.name(false), usage.summary(item));
+        }
+        return;
+    }
+    for item in passwords.iter().filter(|item| item.archive != Some(true)) {
+        match item.user() {
+            user if user.is_empty() => println!("{}", item.name(false)),
+            user => println!("{}  ({})", item.name(false), user),
+        }
+    }
+}
+
+// Entry for an imported code by issuer and account, the only entry of the issuer is confirmed first
+fn match_entry(passwords: &[PasswordData], otp: &Otp) -> Option<usize> {
+    let issuer = otp.issuer.as_deref().unwrap_or(&otp.account);
+    let same = |value: &Option<String>| matches!(value, Some(v) if !otp.account.is_empty() && v.eq_ignore_ascii_case(&otp.account));
+    let found = passwords
+        .iter()
+        .enumerate()
+        .filter(|(_, data)| data.archive != Some(true))
+        .filter(|(_, data)| data.name(false).eq_ignore_ascii_case(issuer))
+        .collect::<Vec<(usize, &PasswordData)>>();
+    if let Some((i, _)) = found
+        .iter()
+        .find(|(_, data)| same(&data.user) || same(&data.email))
+    {
+        return Some(*i);
+    }
+    let [(i, data)] = found.as_slice() else {
+        return None;
+    };
+    let account = match otp.account.as_str() {
+        "" => "no account".to_string(),
+        account => format!("account '{}'", account),
+    };
+    let user = match data.user() {
+        user if user.is_empty() => String::new(),
+        user => format!(" ({})", user),
+    };
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Attach the code for {} to '{}'{}?",
+            account,
+            data.name(false),
+            user
+        ))
+        .default(false)
+        .interact()
+        .unwrap_exit("Read confirmation")
+        .then_some(*i)
+}
+
+// Codes and the number of unsupported entries skipped
+fn read_otps(source: &str) -> (Vec<Otp>, usize) {
+    let text = if source.starts_with("otpauth-migration://") {
+        source.to_string()
+    } else {
+        std::fs::read_to_string(source).unwrap_exit(format!("Read file failed {}", source))
+    };
+    if !text.trim_start().starts_with("otpauth-migration://") {
+        let otps = authenticator::parse_json(&text).unwrap_or_else(|err| exit!("{}", err));
+        return (otps, 0);
+    }
+    // One URI per line, Google Authenticator splits large exports
+    let (mut otps, mut skipped) = (vec![], 0);
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (items, n) =
+            authenticator::parse_migration(line).unwrap_or_else(|err| exit!("{}", err));
+        otps.extend(items);
+        skipped += n;
+    }
+    if skipped > 0 {
+        println!("Skipped {} MD5 entries, they are not supported", skipped);
+    }
+    (otps, skipped)
+}
+
+pub fn import_otp(app: &App, hc: &HomeConfig) {
+    let source = value(app, "import-otp")
+        .unwrap_or_else(|| exit!("Usage: kwrap import-otp <file or otpauth-migration URI>"));
+    let (otps, mut skipped) = read_otps(&source);
+    let mut vault = open_vault(hc);
+    if vault.offline().is_some() {
+        exit!("Server unreachable");
+    }
+    let mut passwords = vault.passwords();
+    // An entry can match several seeds, it is saved once
+    let (mut changed, mut unchanged) = (BTreeSet::new(), 0);
+
+    for otp in otps {
+        let uri = otp.to_uri();
+        // Digits and periods of JSON exports are checked like stored URIs
+        if let Err(err) = Otp::parse(&uri) {
+            let name = otp.issuer.as_deref().unwrap_or(&otp.account);
+            println!("Skipped '{}': {}", name, err);
+            skipped += 1;
+            continue;
+        }
+        let Some(i) = match_entry(&passwords, &otp) else {
+            let name = otp.issuer.clone().unwrap_or_else(|| otp.account.clone());
+            println!("Created '{}'", name);
+            let mut data = PasswordData::default();
+            data.name = Some(name);
+            data.user = Some(otp.account.clone()).filter(|s| !s.is_empty());
+            data.otp = Some(uri);
+            data.updated = Some(timestamp() as u32);
+            passwords.push(data);
+            changed.insert(passwords.len() - 1);
+            continue;
+        };
+
+        let data = &mut passwords[i];
+        let same = data
+            .otp
+            .as_deref()
+            .and_then(|old| Otp::parse(old).ok())
+            .map(|old| old.secret == otp.secret)
+            .unwrap_or(false);
+        if same {
+            unchanged += 1;
+            continue;
+        }
+        if data.otp.is_some() {
+            let replace = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "'{}' has a different one-time password, replace it?",
+                    data.name(false)
+                ))
+                .default(false)
+                .interact()
+                .unwrap_exit("Read confirmation");
+            if !replace {
+                skipped += 1;
+                continue;
+            }
+        }
+        println!("Updated '{}'", data.name(false));
+        data.otp = Some(uri);
+        data.updated = Some(timestamp() as u32);
+        changed.insert(i);
+    }
+
+    let mut items = changed
+        .iter()
+        .map(|&i| passwords[i].clone())
+        .collect::<Vec<PasswordData>>();
+    vault
+        .save_all(&mut items)
+        .unwrap_or_else(|err| exit!("{}", err));
+    println!(
+        "Imported: {} saved, {} unchanged, {} skipped",
+        items.len(),
+        unchanged,
+        skipped
+    );
+}
+
+// Google Authenticator migration URI, or Aegis JSON with `--aegis`
+pub fn export_otp(app: &App, hc: &HomeConfig) {
+    let mut vault = open_vault(hc);
+    let otps = vault
+        .passwords()
+        .iter()
+        .filter(|data| data.archive != Some(true))
+        .filter_map(|data| {
+            let otp = Otp::parse(data.otp.as_deref()?);
+            if let Err(err) = &otp {
+                eprintln!("Skipped '{}': {}", data.name(false), err);
+            }
+            otp.ok()
+        })
+        .collect::<Vec<Otp>>();
+
+    let (mut output, skipped) = if app.value("--aegis").is_some() {
+        (authenticator::to_aegis(&otps), 0)
+    } else {
+        authenticator::to_migration(&otps)
+    };
+    if skipped > 0 {
+        eprintln!(
+            "Skipped {} entries, Google Authenticator only supports 30s TOTP and HOTP with 6 or 8 digits",
+            skipped
+        );
+    }
+
+    match value(app, "-o").map(absolute_path) {
+        Some(path) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options
+                .open(&path)
+                .and_then(|mut f| f.write_all(output.as_bytes()))
+                .unwrap_exit(format!("Write file failed {}", path));
+            println!("Exported {} entries to {}", otps.len() - skipped, path);
+        }
+        None => println!("{}", output),
+    }
+    output.zeroize();
+}
+
+// tag list, tag rename <old> <new>, tag merge <tag>... <into>
+pub fn tag(app: &App, hc: &HomeConfig) {
+    let args = app
+        .value("tag")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let usage = "Usage: kwrap tag list | rename <old> <new> | merge <tag>... <into>";
+    let Some((op, args)) = args.split_first() else {
+        exit!("{}", usage);
+    };
+    let mut vault = open_vault(hc);
+    let mut passwords = vault.passwords();
+    let counts = tag_counts(&passwords);
+
+    let (from, to) = match (op.as_str(), args) {
+        ("list", []) => {
+            for (tag, count) in counts {
+                println!("{} ({})", tag, count);
+            }
+            return;
+        }
+        ("rename", [old, new]) => {
+            if counts.iter().any(|(tag, _)| tag == new) {
+                exit!("Tag '{}' exists, use merge to combine tags", new);
+            }
+            (vec![old.clone()], new.clone())
+        }
+        ("merge", [from @ .., into]) if !from.is_empty() => (from.to_vec(), into.clone()),
+        _ => exit!("{}", usage),
+    };
+    if to.trim().is_empty() {
+        exit!("The new tag is empty");
+    }
+    for tag in &from {
+        if !counts.iter().any(|(name, _)| name == tag) {
+            exit!("No entry has the tag '{}'", tag);
+        }
+    }
+    if vault.offline().is_some() {
+        exit!("Server unreachable, tags cannot be changed offline");
+    }
+
+    let mut changed = passwords
+        .iter_mut()
+        .filter_map(|item| item.rename_tags(&from, &to).then(|| item.clone()))
+        .collect::<Vec<PasswordData>>();
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Change '{}' to '{}' in {} entries?",
+            from.join("', '"),
+            to,
+            changed.len()
+        ))
+        .default(true)
+        .interact()
+        .unwrap_exit("Read confirmation");
+    if !confirmed {
+        return;
+    }
+    vault
+        .save_all(&mut changed)
+        .unwrap_or_else(|err| exit!("{}", err));
+    println!("Updated {} entries", changed.len());
+}
@@ -1,11 +1,14 @@
 use crate::*;
+use argon2::{Algorithm, Argon2, Params, Version};
 use ring::aead::{
-    Aad, BoundKey, Nonce, NonceSequence, OpeningKey, UnboundKey, AES_256_GCM, NONCE_LEN,
+    Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, AES_256_GCM, NONCE_LEN,
 };
 use ring::{
     digest::{digest, SHA256, SHA256_OUTPUT_LEN},
     error::Unspecified,
+    hmac,
     pbkdf2::{derive, PBKDF2_HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
 };
 use std::num::NonZeroU32;
 
@@ -31,6 +34,37 @@ pub fn pbkdf2<P: AsRef<[u8]>, S: AsRef<[u8]>>(
     out
 }
 
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut buf = [0; N];
+    SystemRandom::new()
+        .fill(&mut buf)
+        .unwrap_exit("Generate random bytes");
+    buf
+}
+
+// Memory in KiB
+pub fn argon2id<P: AsRef<[u8]>, S: AsRef<[u8]>>(
+    password: P,
+    salt: S,
+    memory: u32,
+    time: u32,
+    parallelism: u32,
+) -> [u8; 32] {
+    let params = Params::new(memory, time, parallelism, Some(32)).unwrap_exit("Argon2 params");
+    let mut out = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_ref(), salt.as_ref(), &mut out)
+        .unwrap_exit("Argon2 hash");
+    out
+}
+
+// Combine a derived key with the hash of a keyfile
+pub fn keyfile_key<K: AsRef<[u8]>>(key: [u8; 32], keyfile: K) -> [u8; 32] {
+    let hash = digest(&SHA256, keyfile.as_ref());
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), hash.as_ref());
+    tag.as_ref().try_into().unwrap_exit("HMAC-SHA256")
+}
+
 struct StaticNonce([u8; NONCE_LEN]);
 
 impl StaticNonce {
@@ -58,20 +92,91 @@ impl Aes256Gcm {
         Self { key }
     }
 
-    pub fn decrypt<'a>(&self, encrypted: &'a mut [u8]) -> &'a mut [u8] {
+    // Output: NONCE: 12, DATA: N, TAG: 16
+    pub fn encrypt(&self, data: &[u8], aad: &[u8]) -> Vec<u8> {
+        let nonce = random_bytes::<NONCE_LEN>();
         let key = UnboundKey::new(&AES_256_GCM, &self.key).unwrap_exit("AES-GCM Key");
-        let nonce = StaticNonce::new(&encrypted[..NONCE_LEN]).unwrap_exit("AES-GCM Nonce");
+        let mut sealing_key = SealingKey::new(key, StaticNonce(nonce));
+        let mut encrypted = Vec::with_capacity(NONCE_LEN + data.len() + AES_256_GCM.tag_len());
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(data);
+        let tag = sealing_key
+            .seal_in_place_separate_tag(Aad::from(aad), &mut encrypted[NONCE_LEN..])
+            .unwrap_exit("AES-GCM Encrypt");
+        encrypted.extend_from_slice(tag.as_ref());
+        encrypted
+    }
+
+    pub fn encrypt_from<T: Serialize>(&self, value: &T, aad: &[u8]) -> Vec<u8> {
+        let mut json = serde_json::to_vec(value).unwrap_exit("Failed to serialize JSON");
+        let encrypted = self.encrypt(&json, aad);
+        json.zeroize();
+        encrypted
+    }
+
+    pub fn try_decrypt<'a>(
+        &self,
+        encrypted: &'a mut [u8],
+        aad: &[u8],
+    ) -> Result<&'a mut [u8], Unspecified> {
+        let key = UnboundKey::new(&AES_256_GCM, &self.key)?;
+        let nonce = StaticNonce::new(encrypted.get(..NONCE_LEN).ok_or(Unspecified)?)?;
         let mut opening_key = OpeningKey::new(key, nonce);
-        let data = opening_key
-            .open_in_place(Aad::empty(), &mut encrypted[NONCE_LEN..])
-            .unwrap_exit("Password error");
-        data
+        opening_key.open_in_place(Aad::from(aad), &mut encrypted[NONCE_LEN..])
+    }
+
+    pub fn decrypt<'a>(&self, encrypted: &'a mut [u8], aad: &[u8]) -> &'a mut [u8] {
+        self.try_decrypt(encrypted, aad)
+            .unwrap_exit("Password error")
     }
 
-    pub fn decrypt_to<T: DeserializeOwned>(&self, data: &mut [u8]) -> T {
-        let data = self.decrypt(data);
+    pub fn decrypt_to<T: DeserializeOwned>(&self, data: &mut [u8], aad: &[u8]) -> T {
+        let data = self.decrypt(data, aad);
         let json = serde_json::from_slice::<T>(data).unwrap_exit("Failed to parse JSON");
         data.zeroize();
         json
     }
 }
+
+// Look-alike characters such as 0/O and 1/l are left out
+const PASSWORD_CHARS: &[u8] =
+    b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789!#$%&*+-=?@^_";
+
+// Every character is equally likely, passwords of 4 or more have each character class
+pub fn generate_password(length: usize) -> String {
+    let n = PASSWORD_CHARS.len();
+    // Bytes above the last multiple of n would favor the first characters
+    let limit = 256 - 256 % n;
+    loop {
+        let mut password = String::with_capacity(length);
+        while password.len() < length {
+            let mut bytes = random_bytes::<32>();
+            for b in bytes.iter().map(|b| *b as usize).filter(|b| *b < limit) {
+                if password.len() < length {
+                    password.push(PASSWORD_CHARS[b % n] as char);
+                }
+            }
+            bytes.zeroize();
+        }
+        let classes: [fn(&char) -> bool; 4] = [
+            char::is_ascii_uppercase,
+            char::is_ascii_lowercase,
+            char::is_ascii_digit,
+            char::is_ascii_punctuation,
+        ];
+        if length < classes.len()
+            || classes
+                .iter()
+                .all(|class| password.chars().any(|c| class(&c)))
+        {
+            return password;
+        }
+        password.zeroize();
+    }
+}
+
+// Separate key for other data of a vault, such as the usage file
+pub fn subkey(key: &[u8; 32], info: &str) -> [u8; 32] {
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), info.as_bytes());
+    tag.as_ref().try_into().unwrap_exit("HMAC-SHA256")
+}
@@ -0,0 +1,259 @@
+use crate::*;
+use dialoguer::console::style;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+pub struct Field {
+    pub name: String,
+    pub value: String,
+    pub secret: bool,
+}
+
+impl Field {
+    fn new<N: ToString, V: ToString>(name: N, value: V, secret: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            secret,
+        }
+    }
+}
+
+// Entries are matched by server id, by `uid`, or by name and user
+pub fn entry_key(data: &PasswordData) -> String {
+    match (&data.pid, &data.uid) {
+        (Some(id), _) | (None, Some(id)) => id.clone(),
+        (None, None) => name_key(data),
+    }
+}
+
+fn name_key(data: &PasswordData) -> String {
+    format!("{}\n{}", data.name(false), data.user())
+}
+
+// Files saved before `uid` was added still match their entries by name and user
+fn same_name(a: &PasswordData, b: &PasswordData) -> bool {
+    a.pid.is_none()
+        && b.pid.is_none()
+        && (a.uid.is_none() || b.uid.is_none())
+        && name_key(a) == name_key(b)
+}
+
+// Matches each entry of `a` with an entry of `b`, entries with the same key are paired in order.
+// Returns the match of each entry of `a` and the entries of `b` left over, in order
+pub fn pair_entries<'a>(
+    a: &[&PasswordData],
+    b: &[&'a PasswordData],
+) -> (Vec<Option<&'a PasswordData>>, Vec<&'a PasswordData>) {
+    let mut keys = HashMap::<String, VecDeque<usize>>::new();
+    for (i, item) in b.iter().enumerate() {
+        keys.entry(entry_key(item)).or_default().push_back(i);
+    }
+    let mut taken = vec![false; b.len()];
+    let mut matched = a
+        .iter()
+        .map(|item| {
+            let i = keys.get_mut(&entry_key(item))?.pop_front()?;
+            taken[i] = true;
+            Some(b[i])
+        })
+        .collect::<Vec<_>>();
+    for (item, pair) in a.iter().zip(&mut matched) {
+        if pair.is_some() {
+            continue;
+        }
+        let found = (0..b.len()).find(|&i| !taken[i] && same_name(item, b[i]));
+        if let Some(i) = found {
+            taken[i] = true;
+            *pair = Some(b[i]);
+        }
+    }
+    let rest = b
+        .iter()
+        .zip(taken)
+        .filter(|(_, taken)| !taken)
+        .map(|(item, _)| *item)
+        .collect();
+    (matched, rest)
+}
+
+pub fn fields(data: &PasswordData) -> Vec<Field> {
+    let mut fields = vec![];
+    let text = [
+        ("Name", &data.name, false),
+        ("Icon", &data.icon, false),
+        ("User", &data.user, false),
+        ("Email", &data.email, false),
+        ("Phone", &data.phone, false),
+        ("Password", &data.password, true),
+        ("One-time password", &data.otp, true),
+        ("Notes", &data.notes, false),
+    ];
+    for (name, value, secret) in text {
+        if let Some(value) = value {
+            fields.push(Field::new(name, value, secret));
+        }
+    }
+    if let Some(links) = &data.links {
+        fields.push(Field::new("Links", links.join(", "), false));
+    }
+    if let Some(tags) = &data.tags {
+        fields.push(Field::new("Tags", tags.join(", "), false));
+    }
+    if let Some(custom) = &data.custom {
+        for item in custom {
+            let name = format!("Custom '{}'", item.name);
+            fields.push(Field::new(name, &item.value, item.hidden));
+        }
+    }
+    if let Some(pin) = data.pin {
+        fields.push(Field::new("Pin", pin, false));
+    }
+    if data.archive == Some(true) {
+        fields.push(Field::new("Archived", "yes", false));
+    }
+    fields
+}
+
+pub enum FieldChange {
+    Added(Field),
+    Removed(Field),
+    Modified(Field, Field),
+}
+
+pub fn field_changes(old: &PasswordData, new: &PasswordData) -> Vec<FieldChange> {
+    let mut map = BTreeMap::<String, (Option<Field>, Option<Field>)>::new();
+    for field in fields(old) {
+        let name = field.name.clone();
+        map.entry(name).or_default().0 = Some(field);
+    }
+    for field in fields(new) {
+        let name = field.name.clone();
+        map.entry(name).or_default().1 = Some(field);
+    }
+    map.into_values()
+        .filter_map(|pair| match pair {
+            (Some(a), Some(b)) if a.value != b.value => Some(FieldChange::Modified(a, b)),
+            (Some(a), None) => Some(FieldChange::Removed(a)),
+            (None, Some(b)) => Some(FieldChange::Added(b)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn show(field: &Field, reveal: bool) -> String {
+    if field.secret && !reveal {
+        "******".to_string()
+    } else if field.value.contains('\n') {
+        format!("{} lines", field.value.lines().count())
+    } else {
+        format!("'{}'", field.value)
+    }
+}
+
+pub fn print_field_change(change: &FieldChange, reveal: bool) {
+    match change {
+        FieldChange::Added(f) => println!("    {}: added {}", f.name, show(f, reveal)),
+        FieldChange::Removed(f) => println!("    {}: removed", f.name),
+        FieldChange::Modified(a, _) if a.secret && !reveal => {
+            println!("    {} changed", a.name)
+        }
+        FieldChange::Modified(a, b) => {
+            println!("    {}: {} -> {}", a.name, show(a, reveal), show(b, reveal))
+        }
+    }
+}
+
+pub fn diff(old: &[PasswordData], new: &[PasswordData], reveal: bool) {
+    let old = old.iter().collect::<Vec<_>>();
+    let new = new.iter().collect::<Vec<_>>();
+    let (matched, added_items) = pair_entries(&old, &new);
+
+    let (mut added, mut removed, mut modified) = (0, 0, 0);
+    for item in added_items {
+        println!("{} {}", style("+").green(), item.name(false));
+        added += 1;
+    }
+    for (item, other) in old.into_iter().zip(matched) {
+        match other {
+            None => {
+                println!("{} {}", style("-").red(), item.name(false));
+                removed += 1;
+            }
+            Some(other) => {
+                let changes = field_changes(item, other);
+                if changes.is_empty() {
+                    continue;
+                }
+                println!("{} {}", style("~").yellow(), item.name(false));
+                for change in &changes {
+                    print_field_change(change, reveal);
+                }
+                modified += 1;
+            }
+        }
+    }
+    println!(
+        "{} added, {} removed, {} modified",
+        added, removed, modified
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, password: &str) -> PasswordData {
+        let mut data = PasswordData::default();
+        data.name = Some(name.to_string());
+        data.password = Some(password.to_string());
+        data
+    }
+
+    #[test]
+    fn duplicate_keys_pair_in_order() {
+        let a = [
+            entry("Untitled", "1"),
+            entry("Untitled", "2"),
+            entry("Mail", "3"),
+        ];
+        let b = [
+            entry("Untitled", "4"),
+            entry("Bank", "5"),
+            entry("Untitled", "6"),
+        ];
+        let a = a.iter().collect::<Vec<_>>();
+        let b = b.iter().collect::<Vec<_>>();
+        let (matched, rest) = pair_entries(&a, &b);
+        let passwords = matched
+            .iter()
+            .map(|item| item.and_then(|item| item.password.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(passwords, [Some("4"), Some("6"), None]);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].name(false), "Bank");
+    }
+
+    #[test]
+    fn entries_match_by_uid_then_name() {
+        let mut renamed = entry("GitHub", "1");
+        renamed.uid = Some("a".to_string());
+        let mut old = entry("github", "1");
+        old.uid = Some("a".to_string());
+        let saved_before_uid = entry("Mail", "2");
+        let mut mail = entry("Mail", "2");
+        mail.uid = Some("b".to_string());
+        let mut other = entry("Mail", "3");
+        other.uid = Some("c".to_string());
+
+        let a = [&renamed, &mail];
+        let b = [&other, &saved_before_uid, &old];
+        let (matched, rest) = pair_entries(&a, &b);
+        assert_eq!(
+            matched[0].map(|item| item.name(false)),
+            Some("github".to_string())
+        );
+        assert_eq!(matched[1].and_then(|item| item.uid.as_deref()), None);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].uid.as_deref(), Some("c"));
+    }
+}
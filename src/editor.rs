@@ -0,0 +1,520 @@
+use crate::otp::Otp;
+use crate::theme::Theme;
+use crate::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+// Columns of the field labels
+const LABEL_WIDTH: usize = 14;
+
+const HELP: &str = "Tab move  Ctrl-s save  Esc cancel  Ctrl-g generate password\n\
+Ctrl-d remove item  Ctrl-t toggle hidden  Ctrl-r reveal";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Name,
+    User,
+    Email,
+    Phone,
+    Password,
+    Otp,
+    Notes,
+    Link(usize),
+    AddLink,
+    Tag(usize),
+    AddTag,
+    CustomName(usize),
+    CustomValue(usize),
+    AddCustom,
+}
+
+pub enum EditorEvent {
+    None,
+    Save,
+    Cancel,
+}
+
+// Form over the fields of an entry, empty fields are removed on save
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct Editor {
+    // The entry being edited, or an empty one for a new entry
+    pub original: PasswordData,
+    name: String,
+    user: String,
+    email: String,
+    phone: String,
+    password: String,
+    otp: String,
+    notes: String,
+    links: Vec<String>,
+    tags: Vec<String>,
+    custom: Vec<CustomField>,
+    // Selected row and the cursor in characters
+    row: usize,
+    cursor: usize,
+    // Hidden values are shown as typed
+    reveal: bool,
+    password_length: usize,
+    // Last error or notice, shown above the help
+    status: Option<String>,
+}
+
+fn char_index(s: &str, cursor: usize) -> usize {
+    s.char_indices()
+        .nth(cursor)
+        .map(|(i, _)| i)
+        .unwrap_or(s.len())
+}
+
+// Line and column of a cursor in characters
+fn line_col(s: &str, cursor: usize) -> (usize, usize) {
+    let before = s.chars().take(cursor).collect::<String>();
+    let line = before.matches('\n').count();
+    let col = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count();
+    (line, col)
+}
+
+// Cursor of a line and column, the column is clamped to the line
+fn cursor_at(s: &str, line: usize, col: usize) -> usize {
+    let mut cursor = 0;
+    for (i, text) in s.split('\n').enumerate() {
+        let len = text.chars().count();
+        if i == line {
+            return cursor + col.min(len);
+        }
+        cursor += len + 1;
+    }
+    s.chars().count()
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    Some(s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+impl Editor {
+    pub fn new(data: PasswordData, password_length: usize) -> Self {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        let mut editor = Self {
+            name: text(&data.name),
+            user: text(&data.user),
+            email: text(&data.email),
+            phone: text(&data.phone),
+            password: text(&data.password),
+            otp: text(&data.otp),
+            notes: text(&data.notes),
+            links: data.links.clone().unwrap_or_default(),
+            tags: data.tags.clone().unwrap_or_default(),
+            custom: data.custom.clone().unwrap_or_default(),
+            original: data,
+            row: 0,
+            cursor: 0,
+            reveal: false,
+            password_length,
+            status: None,
+        };
+        editor.move_to(0);
+        editor
+    }
+
+    pub fn title(&self) -> String {
+        match self.original.index {
+            Some(_) => format!(" Edit '{}' ", self.original.name(false)),
+            None => " New entry ".to_string(),
+        }
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![
+            Row::Name,
+            Row::User,
+            Row::Email,
+            Row::Phone,
+            Row::Password,
+            Row::Otp,
+            Row::Notes,
+        ];
+        rows.extend((0..self.links.len()).map(Row::Link));
+        rows.push(Row::AddLink);
+        rows.extend((0..self.tags.len()).map(Row::Tag));
+        rows.push(Row::AddTag);
+        for i in 0..self.custom.len() {
+            rows.extend([Row::CustomName(i), Row::CustomValue(i)]);
+        }
+        rows.push(Row::AddCustom);
+        rows
+    }
+
+    fn current(&self) -> Row {
+        self.rows()[self.row]
+    }
+
+    fn value(&self, row: Row) -> Option<&String> {
+        match row {
+            Row::Name => Some(&self.name),
+            Row::User => Some(&self.user),
+            Row::Email => Some(&self.email),
+            Row::Phone => Some(&self.phone),
+            Row::Password => Some(&self.password),
+            Row::Otp => Some(&self.otp),
+            Row::Notes => Some(&self.notes),
+            Row::Link(i) => self.links.get(i),
+            Row::Tag(i) => self.tags.get(i),
+            Row::CustomName(i) => self.custom.get(i).map(|c| &c.name),
+            Row::CustomValue(i) => self.custom.get(i).map(|c| &c.value),
+            Row::AddLink | Row::AddTag | Row::AddCustom => None,
+        }
+    }
+
+    fn value_mut(&mut self, row: Row) -> Option<&mut String> {
+        match row {
+            Row::Name => Some(&mut self.name),
+            Row::User => Some(&mut self.user),
+            Row::Email => Some(&mut self.email),
+            Row::Phone => Some(&mut self.phone),
+            Row::Password => Some(&mut self.password),
+            Row::Otp => Some(&mut self.otp),
+            Row::Notes => Some(&mut self.notes),
+            Row::Link(i) => self.links.get_mut(i),
+            Row::Tag(i) => self.tags.get_mut(i),
+            Row::CustomName(i) => self.custom.get_mut(i).map(|c| &mut c.name),
+            Row::CustomValue(i) => self.custom.get_mut(i).map(|c| &mut c.value),
+            Row::AddLink | Row::AddTag | Row::AddCustom => None,
+        }
+    }
+
+    fn hidden(&self, row: Row) -> bool {
+        match row {
+            Row::Password => true,
+            Row::CustomValue(i) => self.custom[i].hidden,
+            _ => false,
+        }
+    }
+
+    // The cursor starts at the end of the value
+    fn move_to(&mut self, row: usize) {
+        self.row = row.min(self.rows().len() - 1);
+        self.cursor = self
+            .value(self.current())
+            .map(|v| v.chars().count())
+            .unwrap_or_default();
+    }
+
+    // Typing on an "Add" row starts a new item
+    fn add(&mut self, row: Row) -> Row {
+        let added = match row {
+            Row::AddLink => {
+                self.links.push(String::new());
+                Row::Link(self.links.len() - 1)
+            }
+            Row::AddTag => {
+                self.tags.push(String::new());
+                Row::Tag(self.tags.len() - 1)
+            }
+            Row::AddCustom => {
+                self.custom.push(CustomField {
+                    name: String::new(),
+                    value: String::new(),
+                    hidden: false,
+                });
+                Row::CustomName(self.custom.len() - 1)
+            }
+            row => return row,
+        };
+        let i = self.rows().iter().position(|r| *r == added).unwrap_or(0);
+        self.move_to(i);
+        added
+    }
+
+    fn remove(&mut self, row: Row) {
+        match row {
+            Row::Link(i) => {
+                self.links.remove(i).zeroize();
+            }
+            Row::Tag(i) => {
+                self.tags.remove(i);
+            }
+            Row::CustomName(i) | Row::CustomValue(i) => {
+                self.custom.remove(i);
+            }
+            _ => return,
+        }
+        self.move_to(self.row);
+    }
+
+    fn insert(&mut self, c: char) {
+        let row = self.add(self.current());
+        let cursor = self.cursor;
+        if let Some(value) = self.value_mut(row) {
+            value.insert(char_index(value, cursor), c);
+            self.cursor += 1;
+        }
+    }
+
+    fn backspace(&mut self) {
+        let cursor = self.cursor;
+        if cursor == 0 {
+            return;
+        }
+        if let Some(value) = self.value_mut(self.current()) {
+            value.remove(char_index(value, cursor - 1));
+            self.cursor -= 1;
+        }
+    }
+
+    fn delete(&mut self) {
+        let cursor = self.cursor;
+        if let Some(value) = self.value_mut(self.current()) {
+            if cursor < value.chars().count() {
+                value.remove(char_index(value, cursor));
+            }
+        }
+    }
+
+    // Up and down move between lines of the notes before leaving them
+    fn vertical(&mut self, down: bool) {
+        if self.current() == Row::Notes {
+            let (line, col) = line_col(&self.notes, self.cursor);
+            let lines = self.notes.split('\n').count();
+            if down && line + 1 < lines {
+                self.cursor = cursor_at(&self.notes, line + 1, col);
+                return;
+            }
+            if !down && line > 0 {
+                self.cursor = cursor_at(&self.notes, line - 1, col);
+                return;
+            }
+        }
+        if down {
+            self.move_to(self.row + 1);
+        } else {
+            self.move_to(self.row.saturating_sub(1));
+        }
+    }
+
+    fn generate(&mut self) {
+        self.password.zeroize();
+        self.password = generate_password(self.password_length);
+        let i = self.rows().iter().position(|r| *r == Row::Password);
+        self.move_to(i.unwrap_or(0));
+        self.status = Some(format!(
+            "Generated a password of {} characters",
+            self.password_length
+        ));
+    }
+
+    fn toggle_hidden(&mut self, row: Row) {
+        if let Row::CustomName(i) | Row::CustomValue(i) = row {
+            self.custom[i].hidden = !self.custom[i].hidden;
+        }
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> EditorEvent {
+        self.status = None;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let row = self.current();
+        let len = self
+            .value(row)
+            .map(|v| v.chars().count())
+            .unwrap_or_default();
+        match key.code {
+            KeyCode::Esc => return EditorEvent::Cancel,
+            KeyCode::Char('c') if ctrl => return EditorEvent::Cancel,
+            KeyCode::Char('s') if ctrl => return EditorEvent::Save,
+            KeyCode::Char('g') if ctrl => self.generate(),
+            KeyCode::Char('d') if ctrl => self.remove(row),
+            KeyCode::Char('t') if ctrl => self.toggle_hidden(row),
+            KeyCode::Char('r') if ctrl => self.reveal = !self.reveal,
+            KeyCode::Char(_) if ctrl => {}
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Enter if row == Row::Notes => self.insert('\n'),
+            KeyCode::Enter if self.value(row).is_none() => {
+                self.add(row);
+            }
+            KeyCode::Enter | KeyCode::Tab => self.move_to(self.row + 1),
+            KeyCode::BackTab => self.move_to(self.row.saturating_sub(1)),
+            KeyCode::Down => self.vertical(true),
+            KeyCode::Up => self.vertical(false),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            _ => {}
+        }
+        EditorEvent::None
+    }
+
+    // The edited entry, `updated` is left to the caller
+    pub fn to_data(&self) -> Result<PasswordData, String> {
+        if let Some(uri) = non_empty(&self.otp) {
+            Otp::parse(&uri).map_err(|err| format!("One-time password: {}", err))?;
+        }
+        if self
+            .custom
+            .iter()
+            .any(|c| c.name.trim().is_empty() && !c.value.is_empty())
+        {
+            return Err("Custom fields need a name".to_string());
+        }
+
+        let mut data = self.original.clone();
+        data.name = non_empty(&self.name);
+        data.user = non_empty(&self.user);
+        data.email = non_empty(&self.email);
+        data.phone = non_empty(&self.phone);
+        data.otp = non_empty(&self.otp);
+        // Whitespace is kept, it may be part of the password
+        let password = Some(self.password.clone()).filter(|p| !p.is_empty());
+        if password != data.password {
+            match password {
+                Some(password) => data.set_password(password),
+                None => data.password = None,
+            }
+        }
+        data.notes = Some(self.notes.trim_end().to_string()).filter(|n| !n.is_empty());
+        let links = self
+            .links
+            .iter()
+            .filter_map(|link| non_empty(link))
+            .collect::<Vec<String>>();
+        data.links = Some(links).filter(|l| !l.is_empty());
+        let mut tags = vec![];
+        for tag in self.tags.iter().filter_map(|tag| non_empty(tag)) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        data.tags = Some(tags).filter(|t| !t.is_empty());
+        let custom = self
+            .custom
+            .iter()
+            .filter(|c| !c.name.trim().is_empty())
+            .map(|c| CustomField {
+                name: c.name.trim().to_string(),
+                value: c.value.clone(),
+                hidden: c.hidden,
+            })
+            .collect::<Vec<CustomField>>();
+        data.custom = Some(custom).filter(|c| !c.is_empty());
+        Ok(data)
+    }
+
+    fn label(&self, row: Row) -> String {
+        match row {
+            Row::Name => "Name".to_string(),
+            Row::User => "User".to_string(),
+            Row::Email => "Email".to_string(),
+            Row::Phone => "Phone".to_string(),
+            Row::Password => "Password".to_string(),
+            Row::Otp => "OTP URI".to_string(),
+            Row::Notes => "Notes".to_string(),
+            Row::Link(_) => "Link".to_string(),
+            Row::Tag(_) => "Tag".to_string(),
+            Row::CustomName(_) => "Field name".to_string(),
+            Row::CustomValue(i) if self.custom[i].hidden => "  value (h)".to_string(),
+            Row::CustomValue(_) => "  value".to_string(),
+            Row::AddLink => "+ Add link".to_string(),
+            Row::AddTag => "+ Add tag".to_string(),
+            Row::AddCustom => "+ Add field".to_string(),
+        }
+    }
+
+    // Hidden values are masked character by character so the cursor still lines up
+    fn shown(&self, row: Row) -> String {
+        let value = self.value(row).map(|v| v.as_str()).unwrap_or_default();
+        if self.hidden(row) && !self.reveal {
+            "*".repeat(value.chars().count())
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, theme: &Theme) {
+        let size = f.size();
+        let area = Rect::new(
+            size.width / 20,
+            size.height / 20,
+            size.width - size.width / 10,
+            size.height - size.height / 10,
+        );
+        f.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(self.title())
+            .style(theme.text);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(inner);
+
+        let mut lines = vec![];
+        let mut cursor = (0, 0);
+        for (i, row) in self.rows().into_iter().enumerate() {
+            let selected = i == self.row;
+            let label_style = if selected { theme.highlight } else { theme.key };
+            let label = format!("{:<width$}", self.label(row), width = LABEL_WIDTH);
+            let shown = self.shown(row);
+            if selected {
+                let (line, col) = line_col(&shown, self.cursor);
+                let before = shown.split('\n').nth(line).unwrap_or_default();
+                let before = before.chars().take(col).collect::<String>();
+                cursor = (lines.len() + line, LABEL_WIDTH + before.width());
+            }
+            for (j, text) in shown.split('\n').enumerate() {
+                let label = if j == 0 {
+                    Span::styled(label.clone(), label_style)
+                } else {
+                    Span::raw(" ".repeat(LABEL_WIDTH))
+                };
+                lines.push(Spans::from(vec![
+                    label,
+                    Span::styled(text.to_string(), theme.text),
+                ]));
+            }
+        }
+
+        let main = layout[0];
+        let height = main.height as usize;
+        let scroll = (cursor.0 + 1).saturating_sub(height);
+        f.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), main);
+        if self.value(self.current()).is_some() {
+            let x = main.x + (cursor.1 as u16).min(main.width.saturating_sub(1));
+            let y = main.y + (cursor.0 - scroll) as u16;
+            f.set_cursor(x, y);
+        }
+
+        let status = self.status.clone().unwrap_or_default();
+        let footer = vec![
+            Spans::from(Span::styled(status, theme.warn)),
+            Spans::from(Span::styled(
+                HELP.lines().next().unwrap_or_default(),
+                theme.muted,
+            )),
+            Spans::from(Span::styled(
+                HELP.lines().nth(1).unwrap_or_default(),
+                theme.muted,
+            )),
+        ];
+        f.render_widget(Paragraph::new(footer), layout[1]);
+    }
+}
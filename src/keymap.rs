@@ -0,0 +1,384 @@
+use crate::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Quit,
+    PrevTag,
+    NextTag,
+    Up,
+    Down,
+    Left,
+    Right,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    Copy,
+    Reveal,
+    OtpQr,
+    Edit,
+    New,
+    Sort,
+    CheckTag,
+    TagMode,
+    Open,
+    // Whole notes in the notes viewer
+    CopyAll,
+}
+
+const ACTIONS: [(Action, &str); 21] = [
+    (Action::Quit, "quit"),
+    (Action::PrevTag, "prev_tag"),
+    (Action::NextTag, "next_tag"),
+    (Action::Up, "up"),
+    (Action::Down, "down"),
+    (Action::Left, "left"),
+    (Action::Right, "right"),
+    (Action::First, "first"),
+    (Action::Last, "last"),
+    (Action::PageUp, "page_up"),
+    (Action::PageDown, "page_down"),
+    (Action::Copy, "copy"),
+    (Action::Reveal, "reveal"),
+    (Action::OtpQr, "otp_qr"),
+    (Action::Edit, "edit"),
+    (Action::New, "new"),
+    (Action::Sort, "sort"),
+    (Action::CheckTag, "check_tag"),
+    (Action::TagMode, "tag_mode"),
+    (Action::Open, "open"),
+    (Action::CopyAll, "copy_all"),
+];
+
+// Rows of the help panel, paired actions share a row
+const HELP: [(&[Action], &str); 14] = [
+    (&[Action::PrevTag, Action::NextTag], "Toggle tag"),
+    (
+        &[Action::CheckTag, Action::TagMode],
+        "Check tag/match all or any",
+    ),
+    (&[Action::Up, Action::Down], "Toggle selected"),
+    (&[Action::Left, Action::Right], "Toggle list/password"),
+    (&[Action::First, Action::Last], "First/last item"),
+    (&[Action::PageUp, Action::PageDown], "Page up/down"),
+    (&[Action::Copy], "Copy value"),
+    (&[Action::Reveal], "Reveal hidden value"),
+    (&[Action::CopyAll], "Copy all notes"),
+    (&[Action::OtpQr], "Show OTP QR code"),
+    (&[Action::Open], "Open link"),
+    (&[Action::Edit, Action::New], "Edit/new entry"),
+    (&[Action::Sort], "Change sort order"),
+    (&[Action::Quit], "Quit"),
+];
+
+const DEFAULT: [(Action, &str); 22] = [
+    (Action::Quit, "q"),
+    (Action::PrevTag, "a"),
+    (Action::NextTag, "d"),
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Left, "Left"),
+    (Action::Right, "Right"),
+    (Action::First, "Home"),
+    (Action::Last, "End"),
+    (Action::PageUp, "PageUp"),
+    (Action::PageDown, "PageDown"),
+    (Action::Copy, "Enter"),
+    (Action::Reveal, "r"),
+    (Action::OtpQr, "o"),
+    (Action::Edit, "e"),
+    (Action::New, "n"),
+    (Action::Sort, "s"),
+    (Action::CheckTag, "Space"),
+    (Action::TagMode, "m"),
+    (Action::Open, "b"),
+    (Action::CopyAll, "A"),
+    (Action::Quit, "Ctrl-c"),
+];
+
+const VIM: [(Action, &str); 29] = [
+    (Action::Quit, "q"),
+    (Action::PrevTag, "gT"),
+    (Action::NextTag, "gt"),
+    (Action::Up, "k"),
+    (Action::Down, "j"),
+    (Action::Left, "h"),
+    (Action::Right, "l"),
+    (Action::First, "gg"),
+    (Action::Last, "G"),
+    (Action::PageUp, "Ctrl-u"),
+    (Action::PageDown, "Ctrl-d"),
+    (Action::Copy, "y"),
+    (Action::Reveal, "r"),
+    (Action::OtpQr, "o"),
+    (Action::Edit, "e"),
+    (Action::New, "n"),
+    (Action::Sort, "s"),
+    (Action::CheckTag, "Space"),
+    (Action::TagMode, "m"),
+    (Action::Open, "gx"),
+    (Action::CopyAll, "Y"),
+    (Action::Quit, "Ctrl-c"),
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Left, "Left"),
+    (Action::Right, "Right"),
+    (Action::Copy, "Enter"),
+    (Action::PrevTag, "H"),
+    (Action::NextTag, "L"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    // Shift is part of the character itself
+    pub fn from_event(event: KeyEvent) -> Self {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    fn named(name: &str) -> Option<KeyCode> {
+        let code = match name.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    // "q", "Enter", "Ctrl-d", "Alt-x", or a sequence of characters like "gg"
+    fn parse_sequence(s: &str) -> Option<Vec<Self>> {
+        let key = |code, modifiers| Self { code, modifiers };
+        let (modifiers, rest) = if let Some(rest) = s.strip_prefix("Ctrl-") {
+            (KeyModifiers::CONTROL, rest)
+        } else if let Some(rest) = s.strip_prefix("Alt-") {
+            (KeyModifiers::ALT, rest)
+        } else {
+            (KeyModifiers::NONE, s)
+        };
+        if let Some(code) = Self::named(rest) {
+            return Some(vec![key(code, modifiers)]);
+        }
+        let chars = rest.chars().collect::<Vec<char>>();
+        match chars.as_slice() {
+            [] => None,
+            [c] => Some(vec![key(KeyCode::Char(*c), modifiers)]),
+            _ if modifiers == KeyModifiers::NONE => Some(
+                chars
+                    .into_iter()
+                    .map(|c| key(KeyCode::Char(c), modifiers))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|(a, _)| *a == action)
+        .map(|(_, name)| *name)
+        .unwrap_or_default()
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action, String)>,
+    // Keys of an unfinished sequence
+    pending: Vec<Key>,
+}
+
+impl Keymap {
+    // A preset with per-action overrides, invalid entries exit before the UI starts
+    pub fn new(preset: &str, overrides: &BTreeMap<String, Vec<String>>) -> Self {
+        let preset = match preset {
+            "default" => DEFAULT.as_slice(),
+            "vim" => VIM.as_slice(),
+            _ => exit!("Unknown keymap '{}', expected default or vim", preset),
+        };
+        let mut bindings = preset
+            .iter()
+            .map(|(action, key)| (*action, key.to_string()))
+            .collect::<Vec<(Action, String)>>();
+        for (name, keys) in overrides {
+            let action = ACTIONS
+                .iter()
+                .find(|(_, n)| n == name)
+                .map(|(a, _)| *a)
+                .unwrap_or_else(|| exit!("Unknown keymap action '{}'", name));
+            bindings.retain(|(a, _)| *a != action);
+            bindings.extend(keys.iter().map(|key| (action, key.clone())));
+        }
+        let bindings = bindings
+            .into_iter()
+            .map(|(action, key)| {
+                let sequence = Key::parse_sequence(&key).unwrap_or_else(|| {
+                    exit!("Invalid key '{}' for '{}'", key, action_name(action))
+                });
+                (sequence, action, key)
+            })
+            .collect();
+        Self {
+            bindings,
+            pending: vec![],
+        }
+    }
+
+    // Action of a finished sequence, prefixes of longer sequences wait for the next key
+    pub fn action(&mut self, event: KeyEvent) -> Option<Action> {
+        self.pending.push(Key::from_event(event));
+        for _ in 0..2 {
+            if let Some((_, action, _)) = self.bindings.iter().find(|(s, ..)| *s == self.pending) {
+                self.pending.clear();
+                return Some(*action);
+            }
+            if self
+                .bindings
+                .iter()
+                .any(|(s, ..)| s.starts_with(&self.pending))
+            {
+                return None;
+            }
+            // Start over with the last key alone
+            let last = self.pending.pop();
+            self.pending.clear();
+            self.pending.extend(last);
+        }
+        self.pending.clear();
+        None
+    }
+
+    fn first_key(&self, action: Action) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|(_, a, _)| *a == action)
+            .map(|(.., key)| key.as_str())
+    }
+
+    // Key and description of each bound row of the help panel
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        HELP.iter()
+            .filter_map(|(actions, info)| {
+                let keys = actions
+                    .iter()
+                    .map(|action| self.first_key(*action))
+                    .collect::<Option<Vec<&str>>>()?;
+                Some((keys.join("/"), *info))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(keymap: &mut Keymap, keys: &[(KeyCode, KeyModifiers)]) -> Vec<Option<Action>> {
+        keys.iter()
+            .map(|(code, modifiers)| keymap.action(KeyEvent::new(*code, *modifiers)))
+            .collect()
+    }
+
+    fn chars(keymap: &mut Keymap, s: &str) -> Vec<Option<Action>> {
+        let keys = s
+            .chars()
+            .map(|c| {
+                let shift = match c.is_ascii_uppercase() {
+                    true => KeyModifiers::SHIFT,
+                    false => KeyModifiers::NONE,
+                };
+                (KeyCode::Char(c), shift)
+            })
+            .collect::<Vec<_>>();
+        press(keymap, &keys)
+    }
+
+    #[test]
+    fn vim_sequences() {
+        let mut keymap = Keymap::new("vim", &BTreeMap::new());
+        assert_eq!(chars(&mut keymap, "gg"), [None, Some(Action::First)]);
+        assert_eq!(chars(&mut keymap, "gt"), [None, Some(Action::NextTag)]);
+        assert_eq!(chars(&mut keymap, "gT"), [None, Some(Action::PrevTag)]);
+        assert_eq!(chars(&mut keymap, "G"), [Some(Action::Last)]);
+    }
+
+    #[test]
+    fn unfinished_prefix_starts_over() {
+        let mut keymap = Keymap::new("vim", &BTreeMap::new());
+        assert_eq!(chars(&mut keymap, "gj"), [None, Some(Action::Down)]);
+        assert_eq!(chars(&mut keymap, "gzk"), [None, None, Some(Action::Up)]);
+        assert_eq!(chars(&mut keymap, "ggg"), [None, Some(Action::First), None]);
+        assert_eq!(chars(&mut keymap, "g"), [Some(Action::First)]);
+    }
+
+    #[test]
+    fn modifiers() {
+        let mut keymap = Keymap::new("vim", &BTreeMap::new());
+        let ctrl = |c| (KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(
+            press(&mut keymap, &[ctrl('d'), ctrl('u'), ctrl('c')]),
+            [
+                Some(Action::PageDown),
+                Some(Action::PageUp),
+                Some(Action::Quit)
+            ]
+        );
+        // Ctrl-d is not d
+        assert_eq!(chars(&mut keymap, "d"), [None]);
+
+        let alt = Key::parse_sequence("Alt-x").unwrap();
+        assert_eq!(alt[0].modifiers, KeyModifiers::ALT);
+        assert_eq!(alt[0].code, KeyCode::Char('x'));
+        let enter = Key::parse_sequence("Ctrl-Enter").unwrap();
+        assert_eq!(
+            (enter[0].code, enter[0].modifiers),
+            (KeyCode::Enter, KeyModifiers::CONTROL)
+        );
+        assert!(Key::parse_sequence("Ctrl-gg").is_none());
+        assert!(Key::parse_sequence("").is_none());
+    }
+
+    #[test]
+    fn overrides_replace_the_keys_of_an_action() {
+        let overrides = BTreeMap::from([(
+            "copy".to_string(),
+            vec!["x".to_string(), "Alt-c".to_string()],
+        )]);
+        let mut keymap = Keymap::new("default", &overrides);
+        assert_eq!(chars(&mut keymap, "x"), [Some(Action::Copy)]);
+        assert_eq!(
+            press(&mut keymap, &[(KeyCode::Char('c'), KeyModifiers::ALT)]),
+            [Some(Action::Copy)]
+        );
+        assert_eq!(
+            press(&mut keymap, &[(KeyCode::Enter, KeyModifiers::NONE)]),
+            [None]
+        );
+        assert_eq!(chars(&mut keymap, "q"), [Some(Action::Quit)]);
+        assert_eq!(keymap.help()[6], ("x".to_string(), "Copy value"));
+    }
+}
@@ -1,86 +1,355 @@
+use crate::{argon2id, keyfile_key, pbkdf2};
+use std::fmt;
 use std::io::{Error, ErrorKind, Read, Result};
 
 const ID: [u8; 6] = *b"\xffKWRAP";
 
-const VERSION: u8 = 1;
+// V1: ID, VERSION, SALT: 32, ITERATIONS: 4, DATA
+// V2: ID, VERSION, FLAGS: 1, KDF: 1, KDF PARAMS, SALT: 32, DATA
+// The V2 header is authenticated as AES-GCM AAD
+pub const VERSION_1: u8 = 1;
+pub const VERSION_2: u8 = 2;
+pub const LATEST_VERSION: u8 = VERSION_2;
+
+// The key is combined with the hash of a keyfile
+pub const FLAG_KEYFILE: u8 = 0b0000_0001;
+
+const KDF_PBKDF2: u8 = 1;
+const KDF_ARGON2ID: u8 = 2;
+
+// The header is only authenticated after the KDF has run, so its cost is bounded first
+pub const PBKDF2_MAX_ITERATIONS: u32 = 10_000_000;
+pub const ARGON2_MAX_MEMORY: u32 = 4 * 1024 * 1024;
+pub const ARGON2_MAX_TIME: u32 = 64;
+pub const ARGON2_MAX_PARALLELISM: u32 = 16;
 
 // DATA: JSON '[]' 2
 // NONCE: 12, DATA: N, TAG: 16
-const MINIMUM_DATA: usize = 12 + 2 + 16;
+pub const MINIMUM_DATA: usize = 12 + 2 + 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    Pbkdf2 {
+        iterations: u32,
+    },
+    // Memory in KiB
+    Argon2id {
+        memory: u32,
+        time: u32,
+        parallelism: u32,
+    },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self::Argon2id {
+            memory: 64 * 1024,
+            time: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl Kdf {
+    pub fn derive<P: AsRef<[u8]>>(&self, password: P, salt: &[u8; 32]) -> [u8; 32] {
+        match *self {
+            Self::Pbkdf2 { iterations } => pbkdf2(password, salt, iterations),
+            Self::Argon2id {
+                memory,
+                time,
+                parallelism,
+            } => argon2id(password, salt, memory, time, parallelism),
+        }
+    }
+
+    fn to_vec(self) -> Vec<u8> {
+        match self {
+            Self::Pbkdf2 { iterations } => {
+                let mut bytes = vec![KDF_PBKDF2];
+                bytes.extend_from_slice(&iterations.to_be_bytes());
+                bytes
+            }
+            Self::Argon2id {
+                memory,
+                time,
+                parallelism,
+            } => {
+                let mut bytes = vec![KDF_ARGON2ID];
+                bytes.extend_from_slice(&memory.to_be_bytes());
+                bytes.extend_from_slice(&time.to_be_bytes());
+                bytes.extend_from_slice(&parallelism.to_be_bytes());
+                bytes
+            }
+        }
+    }
+}
+
+impl fmt::Display for Kdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pbkdf2 { iterations } => {
+                write!(f, "PBKDF2-HMAC-SHA256, {} iterations", iterations)
+            }
+            Self::Argon2id {
+                memory,
+                time,
+                parallelism,
+            } => write!(
+                f,
+                "Argon2id, {} KiB memory, {} iterations, {} parallelism",
+                memory, time, parallelism
+            ),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct KwrapFile {
     // id: [u8; 6],
-    // version: u8,
+    pub version: u8,
+    pub flags: u8,
+    pub kdf: Kdf,
     pub salt: [u8; 32],
-    pub iterations: u32,
     pub data: Vec<u8>,
 }
 
 impl KwrapFile {
     pub fn parse<R: Read>(mut r: R) -> Result<Self> {
         Self::read_id(&mut r)?;
-        Self::read_vsersion(&mut r)?;
-        let salt = Self::read_salt(&mut r)?;
-        let iterations = Self::read_iterations(&mut r)?;
+        let version = Self::read_version(&mut r)?;
+        let (flags, kdf, salt) = match version {
+            VERSION_1 => {
+                let salt = Self::read_salt(&mut r)?;
+                let iterations = Self::read_iterations(&mut r)?;
+                (0, Kdf::Pbkdf2 { iterations }, salt)
+            }
+            _ => {
+                let flags = Self::read_flags(&mut r)?;
+                let kdf = Self::read_kdf(&mut r)?;
+                (flags, kdf, Self::read_salt(&mut r)?)
+            }
+        };
         let data = Self::read_data(&mut r)?;
         Ok(Self {
             // id: ID,
-            // version: VERSION,
+            version,
+            flags,
+            kdf,
             salt,
-            iterations,
             data,
         })
     }
 
+    fn read_buf<R: Read, const N: usize>(r: &mut R, name: &str) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        r.read_exact(&mut buf).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => invalid(format!("Kwrap file truncated, missing {}", name)),
+            _ => err,
+        })?;
+        Ok(buf)
+    }
+
     fn read_id<R: Read>(r: &mut R) -> Result<()> {
-        let mut buf = [0; 6];
-        r.read_exact(&mut buf)?;
+        let buf = Self::read_buf::<_, 6>(r, "ID")?;
         if buf == ID {
             Ok(())
         } else {
-            Err(Error::new(ErrorKind::Other, "Kwrap file ID Error"))
+            Err(invalid("Not a Kwrap file, ID mismatch"))
         }
     }
 
-    fn read_vsersion<R: Read>(r: &mut R) -> Result<()> {
-        let mut buf = [0; 1];
-        r.read_exact(&mut buf)?;
-        if buf == [VERSION] {
-            Ok(())
-        } else {
-            Err(Error::new(ErrorKind::Other, "Kwrap version Error"))
+    fn read_version<R: Read>(r: &mut R) -> Result<u8> {
+        let [version] = Self::read_buf(r, "version")?;
+        match version {
+            VERSION_1 | VERSION_2 => Ok(version),
+            _ => Err(invalid(format!(
+                "Unsupported Kwrap version {}, latest is {}",
+                version, LATEST_VERSION
+            ))),
+        }
+    }
+
+    fn read_flags<R: Read>(r: &mut R) -> Result<u8> {
+        let [flags] = Self::read_buf(r, "flags")?;
+        if flags & !FLAG_KEYFILE != 0 {
+            return Err(invalid(format!("Unknown Kwrap flags {:#04x}", flags)));
+        }
+        Ok(flags)
+    }
+
+    fn read_u32<R: Read>(r: &mut R, name: &str) -> Result<u32> {
+        let n = u32::from_be_bytes(Self::read_buf(r, name)?);
+        if n == 0 {
+            return Err(invalid(format!("Kwrap {} must not be 0", name)));
+        }
+        Ok(n)
+    }
+
+    fn read_bounded<R: Read>(r: &mut R, name: &str, max: u32) -> Result<u32> {
+        let n = Self::read_u32(r, name)?;
+        if n > max {
+            return Err(invalid(format!(
+                "Kwrap {} {} is above the limit of {}",
+                name, n, max
+            )));
+        }
+        Ok(n)
+    }
+
+    fn read_kdf<R: Read>(r: &mut R) -> Result<Kdf> {
+        match Self::read_buf(r, "KDF")? {
+            [KDF_PBKDF2] => Ok(Kdf::Pbkdf2 {
+                iterations: Self::read_iterations(r)?,
+            }),
+            [KDF_ARGON2ID] => {
+                let memory = Self::read_bounded(r, "Argon2id memory", ARGON2_MAX_MEMORY)?;
+                let time = Self::read_bounded(r, "Argon2id iterations", ARGON2_MAX_TIME)?;
+                let parallelism =
+                    Self::read_bounded(r, "Argon2id parallelism", ARGON2_MAX_PARALLELISM)?;
+                if memory < 8 * parallelism {
+                    return Err(invalid(
+                        "Kwrap Argon2id memory must be at least 8 KiB per lane",
+                    ));
+                }
+                Ok(Kdf::Argon2id {
+                    memory,
+                    time,
+                    parallelism,
+                })
+            }
+            [kdf] => Err(invalid(format!("Unknown Kwrap KDF {}", kdf))),
         }
     }
 
     fn read_salt<R: Read>(r: &mut R) -> Result<[u8; 32]> {
-        let mut buf = [0; 32];
-        r.read_exact(&mut buf)?;
-        Ok(buf)
+        Self::read_buf(r, "salt")
     }
 
     fn read_iterations<R: Read>(r: &mut R) -> Result<u32> {
-        let mut buf = [0; 4];
-        r.read_exact(&mut buf)?;
-        let iterations = u32::from_be_bytes(buf);
-        assert_ne!(iterations, 0);
-        Ok(iterations)
+        Self::read_bounded(r, "PBKDF2 iterations", PBKDF2_MAX_ITERATIONS)
     }
 
     fn read_data<R: Read>(r: &mut R) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
         r.read_to_end(&mut buf)?;
-        assert!(buf.len() >= MINIMUM_DATA);
+        if buf.len() < MINIMUM_DATA {
+            return Err(invalid(format!(
+                "Kwrap data too short, {} bytes, expected at least {}",
+                buf.len(),
+                MINIMUM_DATA
+            )));
+        }
         Ok(buf)
     }
 
-    // pub fn to_vec(&self) -> Vec<u8> {
-    //     let mut bytes = Vec::with_capacity(6 + 1 + 32 + 4 + self.data.len());
-    //     bytes.extend_from_slice(&self.id);
-    //     bytes.extend_from_slice(&[self.version]);
-    //     bytes.extend_from_slice(&self.salt);
-    //     bytes.extend_from_slice(&self.iterations.to_be_bytes());
-    //     bytes.extend_from_slice(&self.data);
-    //     return bytes;
-    // }
+    pub fn keyfile(&self) -> bool {
+        self.flags & FLAG_KEYFILE != 0
+    }
+
+    // The keyfile is only used if the flag is set
+    pub fn key<P: AsRef<[u8]>>(&self, password: P, keyfile: Option<&[u8]>) -> [u8; 32] {
+        let key = self.kdf.derive(password, &self.salt);
+        match keyfile {
+            Some(keyfile) if self.keyfile() => keyfile_key(key, keyfile),
+            _ => key,
+        }
+    }
+
+    pub fn header(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(6 + 1 + 1 + 13 + 32);
+        bytes.extend_from_slice(&ID);
+        bytes.push(self.version);
+        match (self.version, self.kdf) {
+            (VERSION_1, Kdf::Pbkdf2 { iterations }) if self.flags == 0 => {
+                bytes.extend_from_slice(&self.salt);
+                bytes.extend_from_slice(&iterations.to_be_bytes());
+            }
+            (VERSION_1, _) => {
+                return Err(invalid(
+                    "Kwrap version 1 only supports PBKDF2 without flags",
+                ))
+            }
+            (_, kdf) => {
+                bytes.push(self.flags);
+                bytes.extend_from_slice(&kdf.to_vec());
+                bytes.extend_from_slice(&self.salt);
+            }
+        }
+        Ok(bytes)
+    }
+
+    // Additional authenticated data for AES-GCM, version 1 does not bind the header
+    pub fn aad(&self) -> Result<Vec<u8>> {
+        match self.version {
+            VERSION_1 => Ok(vec![]),
+            _ => self.header(),
+        }
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut bytes = self.header()?;
+        bytes.extend_from_slice(&self.data);
+        Ok(bytes)
+    }
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> Error {
+    Error::new(ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argon2_file(memory: u32, time: u32, parallelism: u32) -> Vec<u8> {
+        let mut bytes = ID.to_vec();
+        bytes.extend_from_slice(&[VERSION_2, 0, KDF_ARGON2ID]);
+        bytes.extend_from_slice(&memory.to_be_bytes());
+        bytes.extend_from_slice(&time.to_be_bytes());
+        bytes.extend_from_slice(&parallelism.to_be_bytes());
+        bytes.extend_from_slice(&[0; 32]);
+        bytes.extend_from_slice(&[0; MINIMUM_DATA]);
+        bytes
+    }
+
+    #[test]
+    fn argon2_params_are_bounded() {
+        assert!(KwrapFile::parse(argon2_file(64 * 1024, 3, 4).as_slice()).is_ok());
+        for bytes in [
+            argon2_file(u32::MAX, 3, 4),
+            argon2_file(64 * 1024, 65, 4),
+            argon2_file(64 * 1024, 3, 17),
+            argon2_file(8, 3, 4),
+        ] {
+            let err = KwrapFile::parse(bytes.as_slice()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn pbkdf2_iterations_are_bounded() {
+        let file = |iterations: u32| {
+            let mut bytes = ID.to_vec();
+            bytes.extend_from_slice(&[VERSION_2, 0, KDF_PBKDF2]);
+            bytes.extend_from_slice(&iterations.to_be_bytes());
+            bytes.extend_from_slice(&[0; 32]);
+            bytes.extend_from_slice(&[0; MINIMUM_DATA]);
+            bytes
+        };
+        assert!(KwrapFile::parse(file(100_000).as_slice()).is_ok());
+        let err = KwrapFile::parse(file(u32::MAX).as_slice()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn version_1_header_rejects_argon2() {
+        let kwrap = KwrapFile {
+            version: VERSION_1,
+            flags: 0,
+            kdf: Kdf::default(),
+            salt: [0; 32],
+            data: vec![],
+        };
+        assert_eq!(kwrap.to_vec().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
 }
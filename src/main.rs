@@ -1,16 +1,33 @@
+mod authenticator;
 mod client;
+mod cmd;
 mod crypto;
+mod diff;
+mod editor;
+mod keymap;
 mod kwrap;
+mod merge;
+mod notes;
+mod otp;
+mod settings;
+mod sync;
+mod theme;
 mod ui;
+mod usage;
 mod utils;
 
 use ace::App;
 pub use client::*;
 pub use crypto::*;
 use home_config::HomeConfig;
-pub use kwrap::KwrapFile;
+pub use kwrap::{
+    Kdf, KwrapFile, ARGON2_MAX_MEMORY, ARGON2_MAX_PARALLELISM, ARGON2_MAX_TIME, FLAG_KEYFILE,
+    LATEST_VERSION, MINIMUM_DATA, PBKDF2_MAX_ITERATIONS, VERSION_1,
+};
 pub use serde::{de::DeserializeOwned, Deserialize, Serialize};
+pub use settings::{Settings, Sort};
 pub use ui::start;
+pub use usage::Usage;
 pub use utils::*;
 pub use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -22,13 +39,71 @@ fn main() {
             .config(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
             .cmd("logout", "Clear login info")
             .cmd("info", "Print login info")
+            .cmd(
+                "sync",
+                "Two-way sync a library file with the server: sync <file>",
+            )
+            .cmd(
+                "passwd",
+                "Change the password of a library file: passwd [file]",
+            )
+            .cmd(
+                "upgrade",
+                "Upgrade a library file to the latest format: upgrade [file]",
+            )
+            .cmd("keyfile", "Generate a keyfile: keyfile generate <path>")
+            .cmd("inspect", "Print Kwrap file metadata: inspect <file>")
+            .cmd(
+                "diff",
+                "Compare library files: diff <a> <b> or diff <file> --server",
+            )
+            .cmd(
+                "merge",
+                "Three-way merge library files: merge <base> <local> <remote> [-o <out>]",
+            )
+            .cmd("list", "Print entry names: list [--recent]")
+            .cmd(
+                "open",
+                "Open the first link of an entry and copy its password: open <name>",
+            )
+            .cmd(
+                "tag",
+                "List, rename or merge tags: tag list | rename <old> <new> | merge <tag>... <into>",
+            )
+            .cmd(
+                "rotate",
+                "Change the password of an entry, keeping the old one: rotate <name>",
+            )
+            .cmd(
+                "otp",
+                "Print the one-time password of an entry: otp <name> [--qr]",
+            )
+            .cmd(
+                "import-otp",
+                "Import one-time passwords from Google Authenticator, Aegis or andOTP: import-otp <file or URI>",
+            )
+            .cmd(
+                "export-otp",
+                "Export one-time passwords as a Google Authenticator URI: export-otp [--aegis] [-o <out>]",
+            )
             .cmd("help", "Print help information")
-            .cmd("version", "Print version information");
+            .cmd("version", "Print version information")
+            .opt("--keyfile", "Keyfile of the library file")
+            .opt("--verify", "Try to decrypt when inspecting a file")
+            .opt("--server", "Compare with the logged in server")
+            .opt("--reveal", "Show secret values in the diff")
+            .opt("-o", "Output file of merge and export-otp")
+            .opt("--markers", "Write conflict markers instead of asking")
+            .opt("--qr", "Show the one-time password seed as a QR code")
+            .opt("--aegis", "Export one-time passwords as Aegis JSON")
+            .opt("--recent", "List entries by last use");
 
         if let Some(cmd) = app.command() {
             match cmd.as_str() {
                 "logout" => {
                     hc.delete().unwrap_exit("Delete config file");
+                    HttpCache::delete().unwrap_exit("Delete cache file");
+                    Usage::delete().unwrap_exit("Delete usage file");
                 }
                 "info" => {
                     if hc.path().is_file() {
@@ -38,6 +113,26 @@ fn main() {
                         exit!("Config file does not exist");
                     }
                 }
+                "sync" => {
+                    let path = cmd::value(&app, "sync")
+                        .map(absolute_path)
+                        .unwrap_or_else(|| exit!("Missing library file path"));
+                    let keyfile = cmd::keyfile_path(&app, &hc, &path);
+                    sync::sync(cmd::http_config(&hc), path, keyfile);
+                }
+                "passwd" => cmd::passwd(&app, &hc),
+                "upgrade" => cmd::upgrade(&app, &hc),
+                "keyfile" => cmd::keyfile(&app),
+                "inspect" => cmd::inspect(&app, &hc),
+                "diff" => cmd::diff(&app, &hc),
+                "merge" => cmd::merge(&app, &hc),
+                "list" => cmd::list(&app, &hc),
+                "open" => cmd::open(&app, &hc),
+                "tag" => cmd::tag(&app, &hc),
+                "rotate" => cmd::rotate(&app, &hc),
+                "otp" => cmd::otp(&app, &hc),
+                "import-otp" => cmd::import_otp(&app, &hc),
+                "export-otp" => cmd::export_otp(&app, &hc),
                 "help" => {
                     app.print_help();
                 }
@@ -61,21 +156,11 @@ fn main() {
         })
         .unwrap_or_else(|_| Config::from_stdin());
 
-    let list = match &config {
-        Config::Http(c) => {
-            let mut client = HttpClient::new(c.clone());
-            client.login();
-            client.passwords()
-        }
-        Config::Library(c) => {
-            let client = LibraryClient::new(c);
-            client.passwords
-        }
-    };
+    let vault = Vault::open(&config);
 
     let _ = hc.save_json(&config);
 
     drop(config);
 
-    ui::start(list).unwrap_exit("UI Error")
+    ui::start(vault).unwrap_exit("UI Error")
 }
@@ -0,0 +1,233 @@
+use crate::diff::pair_entries;
+use crate::*;
+use dialoguer::{console::user_attended, theme::ColorfulTheme, Select};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+// Fields whose values are not printed in prompts
+const SECRET_FIELDS: [&str; 2] = ["password", "otp"];
+
+pub struct Merger {
+    interactive: bool,
+    pub conflicts: usize,
+}
+
+fn to_map(data: &PasswordData) -> Map<String, Value> {
+    match serde_json::to_value(data).unwrap_exit("Failed to serialize JSON") {
+        Value::Object(map) => map,
+        _ => unreachable!(),
+    }
+}
+
+fn show(key: &str, value: &Value) -> String {
+    match value {
+        Value::Null => "(empty)".to_string(),
+        _ if SECRET_FIELDS.contains(&key) => "******".to_string(),
+        Value::String(s) => trim_str(s),
+        _ => trim_str(value.to_string()),
+    }
+}
+
+impl Merger {
+    // Conflicts are written as markers if `markers` is set or nobody can answer prompts
+    pub fn new(markers: bool) -> Self {
+        Self {
+            interactive: !markers && user_attended(),
+            conflicts: 0,
+        }
+    }
+
+    fn select(&self, prompt: String, items: &[String]) -> usize {
+        Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items)
+            .default(0)
+            .interact()
+            .unwrap_exit("Read conflict resolution")
+    }
+
+    fn conflict(&mut self, name: &str, key: &str, local: Value, remote: Value) -> Value {
+        // Only strings can hold conflict markers
+        let markers = matches!((&local, &remote), (Value::String(_), Value::String(_)));
+        if self.interactive {
+            let mut items = vec![
+                format!("Keep local: {}", show(key, &local)),
+                format!("Keep remote: {}", show(key, &remote)),
+            ];
+            items.push(match markers {
+                true => "Write conflict markers".to_string(),
+                false => "Keep local and report the conflict".to_string(),
+            });
+            match self.select(format!("Conflict in '{}': {}", name, key), &items) {
+                0 => return local,
+                1 => return remote,
+                _ => {}
+            }
+        }
+        self.conflicts += 1;
+        match (local, remote) {
+            (Value::String(l), Value::String(r)) => {
+                println!("Conflict in '{}': {}, markers written", name, key);
+                Value::String(format!(
+                    "<<<<<<< local\n{}\n=======\n{}\n>>>>>>> remote",
+                    l, r
+                ))
+            }
+            (local, remote) => {
+                println!(
+                    "Conflict in '{}': {}, kept local {}, remote was {}",
+                    name,
+                    key,
+                    show(key, &local),
+                    show(key, &remote)
+                );
+                local
+            }
+        }
+    }
+
+    // Three-way merge of the fields of an entry
+    fn merge_entry(
+        &mut self,
+        base: Option<&PasswordData>,
+        local: &PasswordData,
+        remote: &PasswordData,
+    ) -> PasswordData {
+        let name = local.name(false);
+        let base = base.map(to_map).unwrap_or_default();
+        let (local, remote) = (to_map(local), to_map(remote));
+        let keys = base
+            .keys()
+            .chain(local.keys())
+            .chain(remote.keys())
+            .cloned()
+            .collect::<BTreeSet<String>>();
+
+        let mut merged = Map::new();
+        for key in keys {
+            let b = base.get(&key).cloned().unwrap_or_default();
+            let l = local.get(&key).cloned().unwrap_or_default();
+            let r = remote.get(&key).cloned().unwrap_or_default();
+            let value = if l == r || r == b {
+                l
+            } else if l == b {
+                r
+            } else if key == "updated" {
+                l.as_u64().max(r.as_u64()).into()
+            } else if key == "uid" {
+                // Both sides gave an entry saved before `uid` an id
+                l
+            } else {
+                self.conflict(&name, &key, l, r)
+            };
+            merged.insert(key, value);
+        }
+        serde_json::from_value(Value::Object(merged)).unwrap_exit("Failed to parse JSON")
+    }
+
+    // The entry was changed on one side and deleted on the other
+    fn keep_changed(&mut self, data: &PasswordData, side: &str) -> bool {
+        if self.interactive {
+            let items = ["Keep".to_string(), "Delete".to_string()];
+            let prompt = format!(
+                "Conflict: '{}' was changed {} and deleted on the other side",
+                data.name(false),
+                side
+            );
+            return self.select(prompt, &items) == 0;
+        }
+        println!(
+            "Conflict: '{}' was changed {} and deleted on the other side, kept",
+            data.name(false),
+            side
+        );
+        self.conflicts += 1;
+        true
+    }
+
+    pub fn merge(
+        &mut self,
+        base: &[PasswordData],
+        local: &[PasswordData],
+        remote: &[PasswordData],
+    ) -> Vec<PasswordData> {
+        let base = base.iter().collect::<Vec<_>>();
+        let local = local.iter().collect::<Vec<_>>();
+        let remote = remote.iter().collect::<Vec<_>>();
+        let (local_base, base_rest) = pair_entries(&local, &base);
+        let (local_remote, remote_rest) = pair_entries(&local, &remote);
+        let (remote_base, _) = pair_entries(&remote_rest, &base_rest);
+
+        let mut merged = vec![];
+        for ((item, b), r) in local.into_iter().zip(local_base).zip(local_remote) {
+            match (b, r) {
+                (b, Some(r)) => merged.push(self.merge_entry(b, item, r)),
+                (None, None) => merged.push(item.clone()),
+                (Some(b), None) => {
+                    if b != item && self.keep_changed(item, "locally") {
+                        merged.push(item.clone());
+                    }
+                }
+            }
+        }
+        // Entries that are not in local, in remote order
+        for (item, b) in remote_rest.into_iter().zip(remote_base) {
+            match b {
+                None => merged.push(item.clone()),
+                Some(b) => {
+                    if b != item && self.keep_changed(item, "remotely") {
+                        merged.push(item.clone());
+                    }
+                }
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, notes: &str) -> PasswordData {
+        let mut data = PasswordData::default();
+        data.name = Some(name.to_string());
+        data.notes = Some(notes.to_string());
+        data
+    }
+
+    #[test]
+    fn duplicate_names_merge_with_their_own_entries() {
+        let base = [entry("Untitled", "a"), entry("Untitled", "b")];
+        let local = [entry("Untitled", "a"), entry("Untitled", "b2")];
+        let remote = [entry("Untitled", "a1"), entry("Untitled", "b")];
+        let mut merger = Merger {
+            interactive: false,
+            conflicts: 0,
+        };
+        let merged = merger.merge(&base, &local, &remote);
+        let notes = merged
+            .iter()
+            .map(|item| item.notes.as_deref().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(notes, ["a1", "b2"]);
+        assert_eq!(merger.conflicts, 0);
+    }
+
+    #[test]
+    fn non_string_conflict_is_counted() {
+        let mut base = entry("Mail", "");
+        base.tags = Some(vec!["a".to_string()]);
+        let mut local = base.clone();
+        local.tags = Some(vec!["b".to_string()]);
+        let mut remote = base.clone();
+        remote.tags = Some(vec!["c".to_string()]);
+        let mut merger = Merger {
+            interactive: false,
+            conflicts: 0,
+        };
+        let merged = merger.merge(&[base], &[local], &[remote]);
+        assert_eq!(merged[0].tags, Some(vec!["b".to_string()]));
+        assert_eq!(merger.conflicts, 1);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::theme::Theme;
+use crate::*;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Heading,
+    // Lines between ``` fences, and the fences themselves
+    Fence,
+    Code,
+    Bullet,
+}
+
+// Full-screen view of the notes of an entry, one source line is selected for copying
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct NoteViewer {
+    title: String,
+    lines: Vec<String>,
+    selected: usize,
+    // First visible row and the rows of the last draw
+    scroll: usize,
+    height: usize,
+}
+
+fn kinds(lines: &[String]) -> Vec<Kind> {
+    let mut code = false;
+    lines
+        .iter()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") {
+                code = !code;
+                Kind::Fence
+            } else if code {
+                Kind::Code
+            } else if trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ') {
+                Kind::Heading
+            } else if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+                Kind::Bullet
+            } else {
+                Kind::Text
+            }
+        })
+        .collect()
+}
+
+// Splits at character boundaries, for code and words wider than a row
+fn wrap_chars(text: &str, width: usize) -> Vec<String> {
+    let mut rows = vec![String::new()];
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && used > 0 {
+            rows.push(String::new());
+            used = 0;
+        }
+        rows.last_mut().unwrap().push(c);
+        used += w;
+    }
+    rows
+}
+
+// Greedy word wrap, leading indentation is kept on the first row
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut rows: Vec<String> = vec![];
+    let mut row = String::new();
+    let indent = text.len() - text.trim_start().len();
+    row.push_str(&text[..indent]);
+    for word in text.split_whitespace() {
+        let row_width = row.chars().filter_map(|c| c.width()).sum::<usize>();
+        let word_width = word.chars().filter_map(|c| c.width()).sum::<usize>();
+        let space = usize::from(!row.trim().is_empty());
+        if row_width + space + word_width <= width {
+            if space == 1 {
+                row.push(' ');
+            }
+            row.push_str(word);
+            continue;
+        }
+        if !row.trim().is_empty() {
+            rows.push(std::mem::take(&mut row));
+        }
+        let mut parts = wrap_chars(word, width);
+        row = parts.pop().unwrap_or_default();
+        rows.extend(parts);
+    }
+    rows.push(row);
+    rows
+}
+
+// Text of a rendered line without its Markdown markers
+fn strip(line: &str, kind: Kind) -> String {
+    let trimmed = line.trim_start();
+    match kind {
+        Kind::Heading => trimmed.trim_start_matches('#').trim().to_string(),
+        Kind::Bullet => format!("• {}", &trimmed[2..]),
+        _ => line.to_string(),
+    }
+}
+
+// Inline `code` is styled like code blocks
+fn inline_spans(row: String, style: Style, code: Style) -> Vec<Span<'static>> {
+    if !row.contains('`') {
+        return vec![Span::styled(row, style)];
+    }
+    row.split('`')
+        .enumerate()
+        .filter(|(_, part)| !part.is_empty())
+        .map(|(i, part)| {
+            let style = if i % 2 == 1 { code } else { style };
+            Span::styled(part.to_string(), style)
+        })
+        .collect()
+}
+
+impl NoteViewer {
+    pub fn new(title: String, text: &str) -> Self {
+        Self {
+            title,
+            lines: text.lines().map(|line| line.to_string()).collect(),
+            selected: 0,
+            scroll: 0,
+            height: 0,
+        }
+    }
+
+    pub fn selected(&self) -> (usize, &str) {
+        (
+            self.selected + 1,
+            self.lines
+                .get(self.selected)
+                .map(|l| l.as_str())
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn forward(&mut self, n: usize) {
+        self.selected = (self.selected + n).min(self.lines.len().saturating_sub(1));
+    }
+
+    pub fn back(&mut self, n: usize) {
+        self.selected = self.selected.saturating_sub(n);
+    }
+
+    pub fn first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn last(&mut self) {
+        self.selected = self.lines.len().saturating_sub(1);
+    }
+
+    // Rows of the last draw, for paging
+    pub fn page(&self) -> usize {
+        self.height.max(1)
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, theme: &Theme) {
+        let size = f.size();
+        let area = Rect::new(
+            size.width / 20,
+            size.height / 20,
+            size.width - size.width / 10,
+            size.height - size.height / 10,
+        );
+        f.render_widget(Clear, area);
+        // Empty notes show "line 0/0"
+        let title = format!(
+            " {}, line {}/{} ",
+            self.title,
+            (self.selected + 1).min(self.lines.len()),
+            self.lines.len()
+        );
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .style(theme.text);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        // Line numbers, then the wrapped text
+        let gutter = self.lines.len().max(1).to_string().len() + 3;
+        let width = (inner.width as usize).saturating_sub(gutter).max(1);
+        let heading = theme.key.add_modifier(Modifier::BOLD);
+        let mut rows = vec![];
+        let mut selected_rows = (0, 0);
+        for (i, (line, kind)) in self.lines.iter().zip(kinds(&self.lines)).enumerate() {
+            let text = strip(line, kind);
+            let wrapped = match kind {
+                Kind::Code | Kind::Fence => wrap_chars(&text, width),
+                _ => wrap_words(&text, width),
+            };
+            let style = match kind {
+                Kind::Heading => heading,
+                Kind::Code => theme.digit,
+                Kind::Fence => theme.muted,
+                _ => theme.text,
+            };
+            if i == self.selected {
+                selected_rows = (rows.len(), rows.len() + wrapped.len());
+            }
+            for (j, row) in wrapped.into_iter().enumerate() {
+                let number = if j == 0 {
+                    format!("{:>w$} │ ", i + 1, w = gutter - 3)
+                } else {
+                    format!("{:>w$} │ ", "", w = gutter - 3)
+                };
+                let mut spans = vec![Span::styled(number, theme.muted)];
+                match kind {
+                    Kind::Text | Kind::Bullet => {
+                        spans.extend(inline_spans(row, style, theme.digit))
+                    }
+                    _ => spans.push(Span::styled(row, style)),
+                }
+                if i == self.selected {
+                    spans = spans
+                        .into_iter()
+                        .map(|span| Span::styled(span.content, span.style.patch(theme.highlight)))
+                        .collect();
+                }
+                rows.push(Spans::from(spans));
+            }
+        }
+
+        // The selected line stays in view
+        self.height = inner.height as usize;
+        if selected_rows.0 < self.scroll {
+            self.scroll = selected_rows.0;
+        } else if selected_rows.1 > self.scroll + self.height {
+            self.scroll = selected_rows
+                .1
+                .saturating_sub(self.height)
+                .min(selected_rows.0);
+        }
+        f.render_widget(Paragraph::new(rows).scroll((self.scroll as u16, 0)), inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn headings_need_a_space() {
+        let kinds = kinds(&lines("# Title\n## Part\n#hashtag\n#"));
+        assert_eq!(
+            kinds,
+            [Kind::Heading, Kind::Heading, Kind::Text, Kind::Text]
+        );
+    }
+
+    #[test]
+    fn wrap_cjk() {
+        assert_eq!(wrap_chars("你好世界", 5), ["你好", "世界"]);
+        assert_eq!(wrap_chars("a你b", 2), ["a", "你", "b"]);
+        assert_eq!(wrap_words("你好 世界", 4), ["你好", "世界"]);
+        assert_eq!(wrap_words("你好 世界", 9), ["你好 世界"]);
+    }
+
+    #[test]
+    fn wrap_long_words() {
+        assert_eq!(wrap_chars("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(
+            wrap_words("a abcdefghij b", 4),
+            ["a", "abcd", "efgh", "ij b"]
+        );
+        assert_eq!(wrap_words("  indented text", 10), ["  indented", "text"]);
+    }
+}
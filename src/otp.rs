@@ -0,0 +1,321 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use reqwest::Url;
+use ring::hmac;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+const STEAM_DIGITS: u32 = 5;
+
+// Unreserved URI characters stay readable in the label
+const LABEL: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    Totp { step: u64 },
+    Hotp { counter: u64 },
+    Steam,
+}
+
+// otpauth://totp, otpauth://hotp, otpauth://steam or steam://<secret>
+#[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
+pub struct Otp {
+    #[zeroize(skip)]
+    pub kind: OtpKind,
+    #[zeroize(skip)]
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub secret: Vec<u8>,
+    pub issuer: Option<String>,
+    pub account: String,
+}
+
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>, String> {
+    let secret = secret.replace([' ', '-'], "").to_uppercase();
+    let secret = secret.trim_end_matches('=');
+    match base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret) {
+        Some(bytes) if !bytes.is_empty() => Ok(bytes),
+        _ => Err("Invalid base32 secret".to_string()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} '{}'", name, value))
+}
+
+impl Otp {
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let uri = uri.trim();
+        if let Some(secret) = uri.strip_prefix("steam://") {
+            return Ok(Self {
+                kind: OtpKind::Steam,
+                algorithm: Algorithm::Sha1,
+                digits: STEAM_DIGITS,
+                secret: decode_secret(secret)?,
+                issuer: Some("Steam".to_string()),
+                account: String::new(),
+            });
+        }
+
+        let url = Url::parse(uri).map_err(|_| "Not an otpauth URI".to_string())?;
+        if url.scheme() != "otpauth" {
+            return Err(format!("Unsupported scheme '{}'", url.scheme()));
+        }
+        let mut kind = match url.host_str() {
+            Some("totp") => OtpKind::Totp { step: 30 },
+            Some("hotp") => OtpKind::Hotp { counter: 0 },
+            Some("steam") => OtpKind::Steam,
+            Some(other) => return Err(format!("Unsupported type '{}'", other)),
+            None => return Err("Missing type, expected totp or hotp".to_string()),
+        };
+
+        let label = url.path().trim_start_matches('/');
+        let label = percent_decode_str(label).decode_utf8_lossy();
+        let (mut issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim()),
+            None => (None, label.trim()),
+        };
+
+        let mut secret = None;
+        let mut counter = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = 6;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "issuer" => issuer = Some(value.to_string()),
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => return Err(format!("Unsupported algorithm '{}'", value)),
+                    }
+                }
+                "digits" => digits = parse_number("digits", &value)?,
+                "period" => {
+                    if let OtpKind::Totp { step } = &mut kind {
+                        *step = parse_number("period", &value)?;
+                    }
+                }
+                "counter" => counter = Some(parse_number("counter", &value)?),
+                "encoder" if value == "steam" => kind = OtpKind::Steam,
+                _ => {}
+            }
+        }
+
+        match &mut kind {
+            OtpKind::Totp { step: 0 } => return Err("Invalid period 0".to_string()),
+            OtpKind::Hotp { counter: c } => {
+                *c = counter.ok_or_else(|| "Missing counter".to_string())?;
+            }
+            OtpKind::Steam => digits = STEAM_DIGITS,
+            _ => {}
+        }
+        if !(6..=8).contains(&digits) && kind != OtpKind::Steam {
+            return Err(format!("Unsupported digits {}, expected 6 to 8", digits));
+        }
+        Ok(Self {
+            kind,
+            algorithm,
+            digits,
+            secret: secret.ok_or_else(|| "Missing secret".to_string())?,
+            issuer,
+            account: account.to_string(),
+        })
+    }
+
+    // Seconds per code, HOTP has none
+    pub fn step(&self) -> Option<u64> {
+        match self.kind {
+            OtpKind::Totp { step } => Some(step),
+            OtpKind::Steam => Some(30),
+            OtpKind::Hotp { .. } => None,
+        }
+    }
+
+    // RFC 4226 with dynamic truncation
+    pub fn generate(&self, counter: u64) -> String {
+        let algorithm = match self.algorithm {
+            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::Sha256 => hmac::HMAC_SHA256,
+            Algorithm::Sha512 => hmac::HMAC_SHA512,
+        };
+        let key = hmac::Key::new(algorithm, &self.secret);
+        let hash = hmac::sign(&key, &counter.to_be_bytes());
+        let hash = hash.as_ref();
+        let offset = (hash[hash.len() - 1] & 0xf) as usize;
+        let mut code =
+            u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+
+        if self.kind == OtpKind::Steam {
+            let base = STEAM_ALPHABET.len() as u32;
+            return (0..self.digits)
+                .map(|_| {
+                    let c = STEAM_ALPHABET[(code % base) as usize] as char;
+                    code /= base;
+                    c
+                })
+                .collect();
+        }
+        format!(
+            "{:0width$}",
+            code % 10u32.pow(self.digits),
+            width = self.digits as usize
+        )
+    }
+
+    pub fn to_uri(&self) -> String {
+        let kind = match self.kind {
+            OtpKind::Totp { .. } => "totp",
+            OtpKind::Hotp { .. } => "hotp",
+            OtpKind::Steam => "steam",
+        };
+        let account = utf8_percent_encode(&self.account, LABEL);
+        let label = match &self.issuer {
+            Some(issuer) => format!("{}:{}", utf8_percent_encode(issuer, LABEL), account),
+            None => account.to_string(),
+        };
+        let mut url = Url::parse(&format!("otpauth://{}/{}", kind, label)).unwrap();
+        {
+            let mut query = url.query_pairs_mut();
+            let secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &self.secret);
+            query.append_pair("secret", &secret);
+            if let Some(issuer) = &self.issuer {
+                query.append_pair("issuer", issuer);
+            }
+            let algorithm = match self.algorithm {
+                Algorithm::Sha1 => "SHA1",
+                Algorithm::Sha256 => "SHA256",
+                Algorithm::Sha512 => "SHA512",
+            };
+            query.append_pair("algorithm", algorithm);
+            query.append_pair("digits", &self.digits.to_string());
+            match self.kind {
+                OtpKind::Totp { step } => query.append_pair("period", &step.to_string()),
+                OtpKind::Hotp { counter } => query.append_pair("counter", &counter.to_string()),
+                OtpKind::Steam => query.append_pair("period", "30"),
+            };
+        }
+        url.to_string()
+    }
+
+    // Code for a unix time, or the current HOTP counter
+    pub fn generate_at(&self, time: u64) -> String {
+        match self.kind {
+            OtpKind::Hotp { counter } => self.generate(counter),
+            _ => self.generate(time / self.step().unwrap_or(30)),
+        }
+    }
+}
+
+// Same URI with the HOTP counter replaced
+pub fn set_counter(uri: &str, counter: u64) -> Result<String, String> {
+    let mut url = Url::parse(uri.trim()).map_err(|_| "Not an otpauth URI".to_string())?;
+    let pairs = url
+        .query_pairs()
+        .filter(|(key, _)| key != "counter")
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<(String, String)>>();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("counter", &counter.to_string());
+    Ok(url.to_string())
+}
+
+// QR code of the URI, light modules are drawn so it scans on dark terminals
+pub fn qr_code(uri: &str) -> Result<String, String> {
+    let code = QrCode::new(uri.as_bytes()).map_err(|err| err.to_string())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const SHA256_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+    const SHA512_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+
+    // RFC 4226 Appendix D
+    #[test]
+    fn hotp_rfc4226() {
+        let codes = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        let uri = format!("otpauth://hotp/Test?secret={}&counter=0", SHA1_SECRET);
+        let otp = Otp::parse(&uri).unwrap();
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(otp.generate(counter as u64), *code);
+        }
+    }
+
+    // RFC 6238 Appendix B
+    #[test]
+    fn totp_rfc6238() {
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        let otp = |algorithm: &str, secret: &str| {
+            let uri = format!(
+                "otpauth://totp/Test?secret={}&algorithm={}&digits=8&period=30",
+                secret, algorithm
+            );
+            Otp::parse(&uri).unwrap()
+        };
+        let sha1 = otp("SHA1", SHA1_SECRET);
+        let sha256 = otp("SHA256", SHA256_SECRET);
+        let sha512 = otp("SHA512", SHA512_SECRET);
+        for (time, a, b, c) in vectors {
+            assert_eq!(sha1.generate_at(time), a);
+            assert_eq!(sha256.generate_at(time), b);
+            assert_eq!(sha512.generate_at(time), c);
+        }
+    }
+
+    // The RFC 6238 SHA1 seed in Steam's alphabet
+    #[test]
+    fn steam() {
+        let otp = Otp::parse(&format!("steam://{}", SHA1_SECRET)).unwrap();
+        assert_eq!(otp.generate_at(59), "PV9M4");
+        assert_eq!(otp.generate_at(1234567890), "VHHQY");
+    }
+
+    #[test]
+    fn hotp_counter() {
+        let uri = format!("otpauth://hotp/Test?secret={}", SHA1_SECRET);
+        assert!(Otp::parse(&uri).is_err());
+        let uri = format!("{}&counter={}", uri, u64::MAX - 1);
+        let otp = Otp::parse(&uri).unwrap();
+        assert_eq!(
+            otp.kind,
+            OtpKind::Hotp {
+                counter: u64::MAX - 1
+            }
+        );
+    }
+}
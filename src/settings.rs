@@ -0,0 +1,105 @@
+use crate::*;
+use home_config::{HomeConfig, JsonError};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+// Order of the password list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    Pinned,
+    Name,
+    Updated,
+    Copied,
+}
+
+impl Sort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Pinned => Self::Name,
+            Self::Name => Self::Updated,
+            Self::Updated => Self::Copied,
+            Self::Copied => Self::Pinned,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Pinned => "pinned first",
+            Self::Name => "name A-Z",
+            Self::Updated => "recently updated",
+            Self::Copied => "recently copied",
+        }
+    }
+}
+
+// Preferences of the terminal UI, kept across logouts
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Seconds until a revealed value is hidden again
+    pub reveal_seconds: u64,
+    // Color digits and symbols of revealed values
+    pub reveal_colors: bool,
+    // Key preset, "default" or "vim"
+    pub keymap: String,
+    // Keys per action name, replacing the keys of the preset
+    pub bindings: BTreeMap<String, Vec<String>>,
+    // "dark", "light", "high-contrast" or "monochrome"
+    pub theme: String,
+    // Characters of passwords generated in the editor
+    pub password_length: usize,
+    // Changed from the UI and saved on change
+    pub sort: Sort,
+    // Schemes of links that are opened in the browser
+    pub link_schemes: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            reveal_seconds: 10,
+            reveal_colors: true,
+            keymap: "default".to_string(),
+            bindings: BTreeMap::new(),
+            theme: "dark".to_string(),
+            password_length: 20,
+            sort: Sort::Pinned,
+            link_schemes: vec![
+                "https".to_string(),
+                "http".to_string(),
+                "mailto".to_string(),
+            ],
+        }
+    }
+}
+
+impl Settings {
+    fn home_config() -> HomeConfig {
+        HomeConfig::new(env!("CARGO_PKG_NAME"), "settings.json")
+    }
+
+    // Missing or invalid files fall back to the defaults
+    pub fn read() -> Self {
+        Self::home_config().json::<Self>().unwrap_or_default()
+    }
+
+    // Only `sort` is written, the rest of the file is kept as the user wrote it
+    pub fn save_sort(&self) -> Result<(), String> {
+        let hc = Self::home_config();
+        let mut map = match hc.json::<Value>() {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => return Err("settings.json is not an object".to_string()),
+            Err(JsonError::Io(err)) if err.kind() == ErrorKind::NotFound => Map::new(),
+            Err(JsonError::Io(err)) => return Err(format!("Read settings.json failed {}", err)),
+            Err(JsonError::Serde(err)) => return Err(format!("settings.json is invalid, {}", err)),
+        };
+        let sort = serde_json::to_value(self.sort).map_err(|err| err.to_string())?;
+        map.insert("sort".to_string(), sort);
+        hc.save_json(&map).map_err(|err| match err {
+            JsonError::Io(err) => format!("Write settings.json failed {}", err),
+            JsonError::Serde(err) => err.to_string(),
+        })
+    }
+}
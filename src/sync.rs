@@ -0,0 +1,279 @@
+use crate::*;
+use dialoguer::{theme::ColorfulTheme, Select};
+use home_config::HomeConfig;
+use std::collections::HashMap;
+use std::path::Path;
+use time_humanize::HumanTime;
+
+enum Side {
+    Local,
+    Server,
+    Skip,
+}
+
+// What happens to each entry, decided before anything is written
+#[derive(Debug, PartialEq)]
+enum Step {
+    // Upload the local entry, `true` if it was deleted on the server
+    Create(PasswordData, bool),
+    Update(PasswordData),
+    Pull(PasswordData),
+    Skip(PasswordData),
+    Keep(PasswordData),
+    // Deleted on the server and unchanged locally
+    Remove(PasswordData),
+    // Removed locally and unchanged on the server
+    Delete(PasswordData),
+}
+
+// Time of the last sync for each server, user and library path
+fn sync_state() -> HomeConfig {
+    HomeConfig::new(env!("CARGO_PKG_NAME"), "sync.json")
+}
+
+fn state_key(server: &str, path: &str) -> String {
+    format!("{}\n{}", server, path)
+}
+
+// A new library has never been synced, so nothing on the server counts as removed
+fn last_sync(state: &HashMap<String, u64>, key: &str, created: bool) -> u64 {
+    match created {
+        true => 0,
+        false => state.get(key).copied().unwrap_or_default(),
+    }
+}
+
+// Entries without `updated` are always treated as changed
+fn changed(data: &PasswordData, last: u64) -> bool {
+    !matches!(data.updated, Some(t) if t as u64 <= last)
+}
+
+fn updated(data: &PasswordData) -> String {
+    data.updated
+        .map(|t| HumanTime::from_duration_since_timestamp(t as u64).to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn resolve_conflict(local: &PasswordData, server: &PasswordData) -> Side {
+    let items = [
+        format!("Keep local (updated {})", updated(local)),
+        format!("Keep server (updated {})", updated(server)),
+        "Skip".to_string(),
+    ];
+    let selected = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Conflict: '{}' was changed on both sides",
+            local.name(false)
+        ))
+        .items(&items)
+        .default(0)
+        .interact()
+        .unwrap_exit("Read conflict resolution");
+    match selected {
+        0 => Side::Local,
+        1 => Side::Server,
+        _ => Side::Skip,
+    }
+}
+
+fn plan<F>(
+    local: Vec<PasswordData>,
+    mut server: Vec<PasswordData>,
+    last: u64,
+    mut resolve: F,
+) -> Vec<Step>
+where
+    F: FnMut(&PasswordData, &PasswordData) -> Side,
+{
+    let mut steps = vec![];
+    for local in local {
+        if local.pid.is_none() {
+            steps.push(Step::Create(local, false));
+            continue;
+        }
+
+        let remote = match server.iter().position(|item| item.pid == local.pid) {
+            Some(i) => server.remove(i),
+            None => {
+                if changed(&local, last) {
+                    steps.push(Step::Create(local, true));
+                } else {
+                    steps.push(Step::Remove(local));
+                }
+                continue;
+            }
+        };
+
+        if remote == local {
+            steps.push(Step::Keep(local));
+            continue;
+        }
+
+        let side = match (changed(&local, last), changed(&remote, last)) {
+            (true, false) => Side::Local,
+            (false, true) => Side::Server,
+            _ => resolve(&local, &remote),
+        };
+        steps.push(match side {
+            Side::Local => Step::Update(local),
+            Side::Server => Step::Pull(remote),
+            Side::Skip => Step::Skip(local),
+        });
+    }
+
+    // Entries that only exist on the server
+    for remote in server {
+        if last > 0 && !changed(&remote, last) {
+            steps.push(Step::Delete(remote));
+        } else {
+            steps.push(Step::Pull(remote));
+        }
+    }
+    steps
+}
+
+pub fn sync(config: HttpConfig, path: String, keyfile: Option<String>) {
+    let mut client = HttpClient::new(config);
+    client.login();
+    let server = client.passwords();
+    if client.offline().is_some() {
+        exit!("Server unreachable");
+    }
+
+    let created = !Path::new(&path).is_file();
+    let mut library = if created {
+        println!("Creating {}", path);
+        let config = LibraryConfig::read_new(path.clone(), keyfile);
+        LibraryClient::create(&config, Kdf::default())
+    } else {
+        LibraryClient::new(&LibraryConfig::read(path.clone(), keyfile))
+    };
+
+    let hc = sync_state();
+    let mut state = hc.json::<HashMap<String, u64>>().unwrap_or_default();
+    let key = state_key(&client.id(), &path);
+    let last = last_sync(&state, &key, created);
+
+    let local = std::mem::take(&mut library.passwords);
+    let steps = plan(local, server, last, resolve_conflict);
+
+    let (mut pushed, mut pulled, mut removed, mut skipped) = (0, 0, 0, 0);
+    let mut creates = vec![];
+    let mut updates = vec![];
+    let mut deletes = vec![];
+    for step in steps {
+        match step {
+            Step::Create(data, _) => {
+                creates.push(library.passwords.len());
+                library.passwords.push(data);
+            }
+            Step::Update(data) => {
+                updates.push(library.passwords.len());
+                library.passwords.push(data);
+            }
+            Step::Pull(data) => {
+                println!("Pulled '{}'", data.name(false));
+                pulled += 1;
+                library.passwords.push(data);
+            }
+            Step::Skip(data) => {
+                println!("Skipped '{}'", data.name(false));
+                skipped += 1;
+                library.passwords.push(data);
+            }
+            Step::Keep(data) => library.passwords.push(data),
+            Step::Remove(data) => {
+                println!("Removed '{}' (deleted on server)", data.name(false));
+                removed += 1;
+            }
+            Step::Delete(data) => deletes.push(data),
+        }
+    }
+
+    // The library is written before the server changes and after each new pid,
+    // so an interrupted sync can be run again. The backup keeps the file before the sync
+    library.save().unwrap_or_else(|err| exit!("{}", err));
+    for i in creates {
+        let data = &mut library.passwords[i];
+        let deleted = data.pid.is_some();
+        data.pid = Some(client.create(data).unwrap_or_else(|err| exit!("{}", err)));
+        match deleted {
+            true => println!("Pushed '{}' (deleted on server)", data.name(false)),
+            false => println!("Pushed '{}'", data.name(false)),
+        }
+        pushed += 1;
+        library.resave().unwrap_or_else(|err| exit!("{}", err));
+    }
+    for i in updates {
+        let data = &library.passwords[i];
+        if let Some(pid) = &data.pid {
+            client
+                .update(pid, data)
+                .unwrap_or_else(|err| exit!("{}", err));
+        }
+        println!("Pushed '{}'", data.name(false));
+        pushed += 1;
+    }
+    for data in deletes {
+        if let Some(pid) = &data.pid {
+            client.delete(pid).unwrap_or_else(|err| exit!("{}", err));
+        }
+        println!("Deleted '{}' on server (removed locally)", data.name(false));
+        removed += 1;
+    }
+
+    state.insert(key, timestamp());
+    let _ = hc.save_json(&state);
+
+    println!(
+        "Synced: {} pushed, {} pulled, {} removed, {} conflicts skipped",
+        pushed, pulled, removed, skipped
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: &str, name: &str, updated: u32) -> PasswordData {
+        let mut data = PasswordData::default();
+        data.pid = Some(pid.to_string());
+        data.name = Some(name.to_string());
+        data.updated = Some(updated);
+        data
+    }
+
+    fn conflict(_: &PasswordData, _: &PasswordData) -> Side {
+        panic!("unexpected conflict")
+    }
+
+    #[test]
+    fn first_sync_into_new_file_deletes_nothing() {
+        let mut state = HashMap::new();
+        let key = state_key("https://example.com user", "vault.kwrap");
+        state.insert(key.clone(), 2_000);
+
+        let last = last_sync(&state, &key, true);
+        assert_eq!(last, 0);
+        let server = vec![entry("a", "One", 1_000), entry("b", "Two", 1_500)];
+        let steps = plan(vec![], server, last, conflict);
+        assert_eq!(steps.len(), 2);
+        assert!(steps.iter().all(|step| matches!(step, Step::Pull(_))));
+    }
+
+    #[test]
+    fn state_is_per_server_and_user() {
+        let mut state = HashMap::new();
+        state.insert(state_key("https://a.com alice", "vault.kwrap"), 2_000);
+        let key = state_key("https://a.com bob", "vault.kwrap");
+        assert_eq!(last_sync(&state, &key, false), 0);
+    }
+
+    #[test]
+    fn removed_locally_is_deleted_on_server() {
+        let server = vec![entry("a", "One", 1_000), entry("b", "Two", 3_000)];
+        let steps = plan(vec![], server, 2_000, conflict);
+        assert!(matches!(&steps[0], Step::Delete(data) if data.pid.as_deref() == Some("a")));
+        assert!(matches!(&steps[1], Step::Pull(data) if data.pid.as_deref() == Some("b")));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::*;
+use std::env;
+use tui::style::{Color, Modifier, Style};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Palette {
+    TrueColor,
+    // The 256 xterm colors
+    Indexed,
+    // The 16 ANSI colors
+    Basic,
+    // `NO_COLOR` or the monochrome theme
+    None,
+}
+
+impl Palette {
+    fn detect() -> Self {
+        if env::var_os("NO_COLOR").filter(|v| !v.is_empty()).is_some() {
+            return Self::None;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Indexed
+        } else {
+            Self::Basic
+        }
+    }
+}
+
+// Nearest entry of the xterm 6x6x6 color cube or grayscale ramp
+fn indexed(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, r2) + d(g, g2) + d(b, b2)
+    };
+    let (cr, cg, cb) = (level(r), level(g), level(b));
+    let cube = (
+        LEVELS[cr as usize],
+        LEVELS[cg as usize],
+        LEVELS[cb as usize],
+    );
+    let average = ((r as u16 + g as u16 + b as u16) / 3) as u8;
+    let gray = match average {
+        0..=3 => 0,
+        239.. => 23,
+        _ => (average - 3) / 10,
+    };
+    let value = 8 + 10 * gray;
+    if distance((value, value, value)) < distance(cube) {
+        232 + gray
+    } else {
+        16 + 36 * cr + 6 * cg + cb
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub text: Style,
+    pub muted: Style,
+    pub highlight: Style,
+    pub key: Style,
+    // Offline badge
+    pub alert: Style,
+    pub ok: Style,
+    pub warn: Style,
+    pub danger: Style,
+    pub digit: Style,
+    pub symbol: Style,
+    pub confirm: Style,
+    // Light modules of QR codes are drawn in the foreground
+    pub qr: Style,
+}
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+impl Theme {
+    // Unknown names exit before the UI starts
+    pub fn new(name: &str) -> Self {
+        let palette = Palette::detect();
+        if palette == Palette::None {
+            return Self::monochrome();
+        }
+        // Other terminals get the nearest indexed or ANSI color
+        let rgb = |r, g, b, basic| match palette {
+            Palette::TrueColor => Color::Rgb(r, g, b),
+            Palette::Indexed => Color::Indexed(indexed(r, g, b)),
+            _ => basic,
+        };
+        let bold = Modifier::BOLD;
+        match name {
+            "dark" => Self {
+                text: fg(Color::White),
+                muted: fg(Color::Gray),
+                highlight: Style::default()
+                    .bg(rgb(54, 132, 246, Color::Blue))
+                    .add_modifier(bold),
+                key: fg(Color::LightCyan),
+                alert: fg(Color::White).bg(Color::Red).add_modifier(bold),
+                ok: fg(Color::Green),
+                warn: fg(Color::Yellow),
+                danger: fg(Color::Red),
+                digit: fg(Color::LightCyan),
+                symbol: fg(Color::LightMagenta),
+                confirm: fg(Color::Yellow).bg(Color::Black),
+                qr: fg(Color::White).bg(Color::Black),
+            },
+            "light" => Self {
+                text: fg(Color::Black),
+                muted: fg(Color::DarkGray),
+                highlight: fg(Color::White)
+                    .bg(rgb(54, 132, 246, Color::Blue))
+                    .add_modifier(bold),
+                key: fg(Color::Blue),
+                alert: fg(Color::White).bg(Color::Red).add_modifier(bold),
+                ok: fg(rgb(0, 128, 0, Color::Green)),
+                warn: fg(rgb(176, 112, 0, Color::Yellow)),
+                danger: fg(Color::Red),
+                digit: fg(Color::Blue),
+                symbol: fg(Color::Magenta),
+                confirm: fg(Color::Black).bg(Color::White),
+                qr: fg(Color::White).bg(Color::Black),
+            },
+            "high-contrast" => Self {
+                text: fg(Color::White),
+                muted: fg(Color::White),
+                highlight: fg(Color::Black).bg(Color::Yellow).add_modifier(bold),
+                key: fg(Color::Yellow).add_modifier(bold),
+                alert: fg(Color::Black).bg(Color::Yellow).add_modifier(bold),
+                ok: fg(Color::LightGreen).add_modifier(bold),
+                warn: fg(Color::Yellow).add_modifier(bold),
+                danger: fg(Color::LightRed).add_modifier(bold),
+                digit: fg(Color::LightCyan).add_modifier(bold),
+                symbol: fg(Color::Yellow).add_modifier(bold),
+                confirm: fg(Color::Yellow).bg(Color::Black),
+                qr: fg(Color::White).bg(Color::Black),
+            },
+            "monochrome" => Self::monochrome(),
+            _ => exit!(
+                "Unknown theme '{}', expected dark, light, high-contrast or monochrome",
+                name
+            ),
+        }
+    }
+
+    // Only text attributes, the terminal keeps its own colors
+    fn monochrome() -> Self {
+        let plain = Style::default();
+        Self {
+            text: plain,
+            muted: plain,
+            highlight: plain.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            key: plain.add_modifier(Modifier::BOLD),
+            alert: plain.add_modifier(Modifier::REVERSED),
+            ok: plain,
+            warn: plain,
+            danger: plain.add_modifier(Modifier::BOLD),
+            digit: plain.add_modifier(Modifier::UNDERLINED),
+            symbol: plain.add_modifier(Modifier::BOLD),
+            confirm: plain,
+            qr: plain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_indexed_color() {
+        assert_eq!(indexed(0, 0, 0), 16);
+        assert_eq!(indexed(255, 255, 255), 231);
+        assert_eq!(indexed(128, 128, 128), 244);
+        assert_eq!(indexed(54, 132, 246), 69);
+        assert_eq!(indexed(176, 112, 0), 130);
+    }
+}
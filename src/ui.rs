@@ -1,24 +1,38 @@
+use crate::editor::{Editor, EditorEvent};
+use crate::keymap::{Action, Keymap};
+use crate::notes::NoteViewer;
+use crate::otp;
+use crate::theme::Theme;
 use crate::*;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
 use std::{
     io::{self, Result as IoResult},
     time::{Duration, Instant},
 };
+use time_humanize::HumanTime;
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Cell, List, ListItem, ListState, Row, Table, Tabs},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Tabs, Wrap,
+    },
     Frame, Terminal,
 };
+use unicode_width::UnicodeWidthStr;
 
 struct AppState {
+    vault: Vault,
     passwords: Vec<PasswordData>,
     tag: Tag,
     list: ListData<PasswordData>,
@@ -26,57 +40,678 @@ struct AppState {
     view: SelectedView,
     logs: Vec<Log>,
     lock: Instant,
+    offline: Option<u64>,
+    // Password history of the selected entry is expanded
+    history: bool,
+    popup: Popup,
+    // Entry being edited, drawn over everything else
+    editor: Option<Editor>,
+    // Notes of the selected entry, drawn over the panes
+    notes: Option<NoteViewer>,
+    // Revealed hidden value of the preview and when it was revealed
+    reveal: Option<(usize, Instant)>,
+    settings: Settings,
+    keymap: Keymap,
+    theme: Theme,
+    // Areas of the last draw, for mouse events
+    areas: Areas,
+    // Time, pane and row of the last left click
+    click: Option<(Instant, Pane, usize)>,
+    // Logs scrolled up from the latest
+    log_scroll: usize,
+    usage: Usage,
+}
+
+#[derive(Default)]
+struct Areas {
+    tags: Rect,
+    list: Option<Rect>,
+    preview: Option<Rect>,
+    logs: Option<Rect>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    List,
+    Preview,
+}
+
+// Rows of each list and preview item
+const ITEM_HEIGHT: usize = 3;
+// Logs kept for scrolling
+const LOG_LIMIT: usize = 100;
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+// Entries of the "Recent" tag
+const RECENT_LIMIT: usize = 10;
+
+enum Popup {
+    None,
+    // The QR code exposes the seed, so it is confirmed first
+    ConfirmQr(String),
+    Qr(String, String),
+}
+
+impl AppState {
+    fn log(&mut self, message: String) {
+        if self.logs.len() >= LOG_LIMIT {
+            self.logs.remove(0);
+        }
+        self.logs.push(Log::new(message));
+        self.log_scroll = 0;
+    }
+
+    // Copy the selected preview value, or run its action
+    fn activate(&mut self) {
+        let Some(i) = self.preview.state.selected() else {
+            return;
+        };
+        let item = &self.preview.items[i];
+        let msg = match item.action {
+            DisplayAction::ToggleHistory => {
+                self.history = !self.history;
+                return;
+            }
+            DisplayAction::Hotp => self.use_hotp(),
+            DisplayAction::Notes => {
+                let text = item.copy_value.clone();
+                let title = match self.selected() {
+                    Some(data) => format!("Notes of '{}'", data.name(false)),
+                    None => "Notes".to_string(),
+                };
+                self.notes = Some(NoteViewer::new(title, &text));
+                return;
+            }
+            DisplayAction::Copy => match copy_text(&item.copy_value) {
+                Ok(_) => {
+                    let field = item.key.trim().to_string();
+                    self.mark_copied(&field);
+                    format!("Copied '{}'", field)
+                }
+                Err(msg) => format!("Failed '{}'", msg),
+            },
+        };
+        self.log(msg);
+    }
+
+    // Keys of the notes viewer, Esc or left closes it
+    fn notes_key(&mut self, key: KeyEvent) {
+        let Some(notes) = &mut self.notes else {
+            return;
+        };
+        let action = match key.code {
+            KeyCode::Esc => Action::Quit,
+            _ => match self.keymap.action(key) {
+                Some(action) => action,
+                None => return,
+            },
+        };
+        let copied = match action {
+            Action::Quit | Action::Left => {
+                self.notes = None;
+                return;
+            }
+            Action::Up => return notes.back(1),
+            Action::Down => return notes.forward(1),
+            Action::PageUp => return notes.back(notes.page()),
+            Action::PageDown => return notes.forward(notes.page()),
+            Action::First => return notes.first(),
+            Action::Last => return notes.last(),
+            Action::Copy => {
+                let (line, text) = notes.selected();
+                copy_text(text).map(|_| format!("Copied line {} of notes", line))
+            }
+            Action::CopyAll => copy_text(notes.text()).map(|_| "Copied 'Notes'".to_string()),
+            _ => return,
+        };
+        let msg = match copied {
+            Ok(msg) => {
+                self.mark_copied("Notes");
+                msg
+            }
+            Err(msg) => format!("Failed '{}'", msg),
+        };
+        self.log(msg);
+    }
+
+    fn mark_copied(&mut self, field: &str) {
+        let Some(i) = self.list.state.selected() else {
+            return;
+        };
+        let index = self.list.items[i].index;
+        self.usage.record(&self.list.items[i], field);
+        self.usage.save();
+        // The Recent tab is reordered, the copied entry stays selected
+        if self.tag.selected == SelectedTag::Recent {
+            self.filter_list();
+            let position = self.list.items.iter().position(|p| p.index == index);
+            self.list.state.select(position.or(Some(0)));
+        }
+    }
+
+    fn copy_password(&mut self) {
+        let Some(data) = self.selected() else {
+            return;
+        };
+        let msg = match &data.password {
+            Some(password) => match copy_text(password) {
+                Ok(_) => {
+                    let msg = format!("Copied password of '{}'", data.name(false));
+                    self.mark_copied("Password");
+                    msg
+                }
+                Err(msg) => format!("Failed '{}'", msg),
+            },
+            None => format!("'{}' has no password", data.name(false)),
+        };
+        self.log(msg);
+    }
+
+    fn select_tag(&mut self, selected: SelectedTag) {
+        self.tag.selected = selected;
+        self.view = SelectedView::List;
+        self.list.unselect();
+        self.preview.unselect();
+        self.history = false;
+    }
+
+    fn mouse(&mut self, event: MouseEvent) {
+        let (x, y) = (event.column, event.row);
+        if let Some(notes) = &mut self.notes {
+            match event.kind {
+                MouseEventKind::ScrollDown => notes.forward(1),
+                MouseEventKind::ScrollUp => notes.back(1),
+                _ => {}
+            }
+            return;
+        }
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.left_click(x, y),
+            MouseEventKind::ScrollDown => self.scroll(x, y, true),
+            MouseEventKind::ScrollUp => self.scroll(x, y, false),
+            _ => {}
+        }
+    }
+
+    fn pane_at(&self, x: u16, y: u16) -> Option<Pane> {
+        let inside = |area: Option<Rect>| area.filter(|a| contains(*a, x, y)).is_some();
+        if inside(self.areas.list) {
+            Some(Pane::List)
+        } else if inside(self.areas.preview) {
+            Some(Pane::Preview)
+        } else {
+            None
+        }
+    }
+
+    fn left_click(&mut self, x: u16, y: u16) {
+        if contains(self.areas.tags, x, y) {
+            if let Some(selected) = self.tag.at(x - self.areas.tags.x) {
+                self.select_tag(selected);
+            }
+            return;
+        }
+        let Some(pane) = self.pane_at(x, y) else {
+            return;
+        };
+        let (area, offset, len) = match pane {
+            Pane::List => (
+                self.areas.list.unwrap_or_default(),
+                self.list.offset,
+                self.list.items.len(),
+            ),
+            Pane::Preview => (
+                self.areas.preview.unwrap_or_default(),
+                self.preview.offset,
+                self.preview.items.len(),
+            ),
+        };
+        // Inside the borders
+        if y <= area.y || y + 1 >= area.y + area.height {
+            return;
+        }
+        let i = offset + (y - area.y - 1) as usize / ITEM_HEIGHT;
+        if i >= len {
+            return;
+        }
+
+        let double = matches!(
+            self.click,
+            Some((time, p, j)) if p == pane && j == i && time.elapsed() < DOUBLE_CLICK
+        );
+        self.click = Some((Instant::now(), pane, i));
+        match pane {
+            Pane::List => {
+                if self.list.state.selected() != Some(i) {
+                    self.history = false;
+                    self.preview.unselect();
+                }
+                self.list.state.select(Some(i));
+                self.view = SelectedView::List;
+                if double {
+                    self.copy_password();
+                }
+            }
+            Pane::Preview => {
+                self.preview.state.select(Some(i));
+                self.view = SelectedView::Preview;
+                if double {
+                    self.click = None;
+                    self.activate();
+                }
+            }
+        }
+    }
+
+    fn scroll(&mut self, x: u16, y: u16, down: bool) {
+        if self.areas.logs.filter(|a| contains(*a, x, y)).is_some() {
+            self.log_scroll = if down {
+                self.log_scroll.saturating_sub(1)
+            } else {
+                (self.log_scroll + 1).min(self.logs.len().saturating_sub(1))
+            };
+            return;
+        }
+        match (self.pane_at(x, y), down) {
+            (Some(Pane::List), down) => {
+                if down {
+                    self.list.forward(1);
+                } else {
+                    self.list.back(1);
+                }
+                self.history = false;
+                self.reveal = None;
+                self.preview.unselect();
+            }
+            (Some(Pane::Preview), true) => self.preview.forward(1),
+            (Some(Pane::Preview), false) => self.preview.back(1),
+            (None, _) => {}
+        }
+    }
+
+    // Entries of the selected tag
+    fn filter_list(&mut self) {
+        if self.tag.selected == SelectedTag::Recent {
+            self.list.items = self
+                .usage
+                .recent(&self.passwords, RECENT_LIMIT)
+                .into_iter()
+                .cloned()
+                .collect();
+            return;
+        }
+        self.list.items = self
+            .passwords
+            .iter()
+            .filter(|item| self.tag.matches(item))
+            .cloned()
+            .collect();
+    }
+
+    // Select an entry by its vault index, or keep the selection within the list if the
+    // selected tag no longer shows it
+    fn select_entry(&mut self, index: usize) {
+        self.filter_list();
+        let position = self.list.items.iter().position(|p| p.index == Some(index));
+        let last = self.list.items.len().checked_sub(1);
+        let position = match position {
+            Some(i) => Some(i),
+            None => self
+                .list
+                .state
+                .selected()
+                .zip(last)
+                .map(|(i, last)| i.min(last)),
+        };
+        self.list.state.select(position);
+        self.preview.unselect();
+        self.view = SelectedView::List;
+    }
+
+    // The selected link of the preview, or the first link of the entry
+    fn open_link(&mut self) {
+        let selected = self
+            .preview
+            .state
+            .selected()
+            .map(|i| &self.preview.items[i]);
+        let link = match selected {
+            Some(item) if self.view == SelectedView::Preview && item.link => {
+                Some(item.copy_value.clone())
+            }
+            _ => self
+                .selected()
+                .and_then(|data| data.links.as_ref()?.first().cloned()),
+        };
+        let Some(link) = link else {
+            return self.log("No link to open".to_string());
+        };
+        let msg = match open_link(&link, &self.settings.link_schemes) {
+            Ok(url) => format!("Opened link to {}", url.host_str().unwrap_or(url.scheme())),
+            Err(err) => format!("Failed '{}'", err),
+        };
+        self.log(msg);
+    }
+
+    fn check_tag(&mut self) {
+        let Some(name) = self.tag.toggle_checked() else {
+            return self.log("Select a tag to check it".to_string());
+        };
+        let msg = if self.tag.checked.contains(&name) {
+            format!("Checked '{}'", name)
+        } else {
+            format!("Unchecked '{}'", name)
+        };
+        self.select_tag(self.tag.selected);
+        self.log(msg);
+    }
+
+    fn toggle_tag_mode(&mut self) {
+        self.tag.any = !self.tag.any;
+        self.select_tag(self.tag.selected);
+        let mode = if self.tag.any { "any" } else { "all" };
+        self.log(format!("Entries match {} checked tags", mode));
+    }
+
+    // The selected entry stays selected
+    fn cycle_sort(&mut self) {
+        let selected = self.selected().and_then(|data| data.index);
+        self.settings.sort = self.settings.sort.next();
+        sort_passwords(&mut self.passwords, self.settings.sort, &self.usage);
+        if let Some(index) = selected {
+            self.select_entry(index);
+        }
+        self.log(format!("Sorted by {}", self.settings.sort.title()));
+        if let Err(err) = self.settings.save_sort() {
+            self.log(format!("Sort order not saved: {}", err));
+        }
+    }
+
+    fn edit(&mut self, data: PasswordData) {
+        if self.offline.is_some() {
+            return self.log("Offline, the cache is read-only".to_string());
+        }
+        self.editor = Some(Editor::new(data, self.settings.password_length));
+    }
+
+    // Failures keep the editor open with the error
+    fn save_edit(&mut self) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        let mut data = match editor.to_data() {
+            Ok(data) => data,
+            Err(err) => return editor.set_status(err),
+        };
+        let name = data.name(false);
+        if data == editor.original {
+            self.editor = None;
+            return self.log(format!("No changes to '{}'", name));
+        }
+        let new = data.index.is_none();
+        data.updated = Some(timestamp() as u32);
+        if let Err(err) = self.vault.save(&mut data) {
+            editor.set_status(format!("Failed '{}'", err));
+            return self.log(format!("Failed to save '{}': {}", name, err));
+        }
+        self.editor = None;
+
+        // Server entries are numbered by the UI, library entries by the vault
+        let index = *data.index.get_or_insert(self.passwords.len());
+        match self.passwords.iter_mut().find(|p| p.index == Some(index)) {
+            Some(item) => *item = data,
+            None => self.passwords.push(data),
+        }
+        // Tags are recounted, the selected tag may have moved
+        self.tag.recount(&self.passwords);
+        if new {
+            self.select_tag(SelectedTag::All);
+        }
+        sort_passwords(&mut self.passwords, self.settings.sort, &self.usage);
+        self.select_entry(index);
+        let verb = if new { "Created" } else { "Saved" };
+        self.log(format!("{} '{}'", verb, name));
+    }
+
+    fn selected(&self) -> Option<&PasswordData> {
+        self.list.state.selected().map(|i| &self.list.items[i])
+    }
+
+    fn confirm_qr(&mut self) {
+        let Some(data) = self.selected() else {
+            return;
+        };
+        if data.otp.is_none() {
+            let msg = format!("'{}' has no one-time password", data.name(false));
+            return self.log(msg);
+        }
+        self.popup = Popup::ConfirmQr(data.name(false));
+    }
+
+    fn show_qr(&mut self) {
+        let Some(uri) = self.selected().and_then(|data| data.otp.clone()) else {
+            return;
+        };
+        let name = self
+            .selected()
+            .map(|data| data.name(false))
+            .unwrap_or_default();
+        match otp::Otp::parse(&uri).and_then(|otp| otp::qr_code(&otp.to_uri())) {
+            Ok(qr) => self.popup = Popup::Qr(name, qr),
+            Err(err) => self.log(format!("Failed '{}'", err)),
+        }
+    }
+
+    fn toggle_reveal(&mut self) {
+        let selected = self.preview.state.selected();
+        self.reveal = match (self.reveal, selected) {
+            (None, Some(i)) if self.preview.items[i].hidden => Some((i, Instant::now())),
+            _ => None,
+        };
+    }
+
+    fn close_popup(&mut self) {
+        if let Popup::Qr(_, qr) = &mut self.popup {
+            qr.zeroize();
+        }
+        self.popup = Popup::None;
+    }
+
+    // Copy the next HOTP code once the counter is saved
+    fn use_hotp(&mut self) -> String {
+        let Some(i) = self.list.state.selected() else {
+            return "No entry selected".to_string();
+        };
+        let mut data = self.list.items[i].clone();
+        let code = match data.next_hotp() {
+            Ok(code) => code,
+            Err(err) => return format!("Failed '{}'", err),
+        };
+        if let Err(err) = self.vault.save(&mut data) {
+            return format!("Failed '{}'", err);
+        }
+        if let Some(item) = self.passwords.iter_mut().find(|p| p.index == data.index) {
+            *item = data;
+        }
+        match copy_text(&code) {
+            Ok(_) => {
+                self.mark_copied("One-time password");
+                "Copied 'One-time password', counter saved".to_string()
+            }
+            Err(msg) => format!("Failed '{}'", msg),
+        }
+    }
 }
 
 struct Tag {
     selected: SelectedTag,
-    tags: Vec<String>,
+    // Tags of entries that are not archived, with their counts
+    tags: Vec<(String, usize)>,
+    untagged: usize,
+    // Checked tags filter together, by name so they survive a recount
+    checked: BTreeSet<String>,
+    // Entries with any checked tag instead of all of them
+    any: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum SelectedTag {
     All,
+    // Last used entries
+    Recent,
+    Untagged,
     Archive,
     Tag(usize),
 }
 
+// Tabs before the tags of the entries
+const FIXED_TAGS: [SelectedTag; 4] = [
+    SelectedTag::All,
+    SelectedTag::Recent,
+    SelectedTag::Untagged,
+    SelectedTag::Archive,
+];
+
 impl Tag {
-    fn next(&mut self) {
-        self.selected = match self.selected {
-            SelectedTag::All => SelectedTag::Archive,
-            SelectedTag::Archive => {
-                if self.tags.is_empty() {
-                    SelectedTag::All
-                } else {
-                    SelectedTag::Tag(0)
-                }
-            }
-            SelectedTag::Tag(i) => {
-                if i < self.tags.len() - 1 {
-                    SelectedTag::Tag(i + 1)
-                } else {
-                    SelectedTag::All
-                }
+    fn new(passwords: &[PasswordData]) -> Self {
+        let mut tag = Self {
+            selected: SelectedTag::All,
+            tags: vec![],
+            untagged: 0,
+            checked: BTreeSet::new(),
+            any: false,
+        };
+        tag.recount(passwords);
+        tag
+    }
+
+    // Tags that are gone are unchecked, a selected one falls back to All
+    fn recount(&mut self, passwords: &[PasswordData]) {
+        let current = passwords.iter().filter(|item| item.archive != Some(true));
+        let name = match self.selected {
+            SelectedTag::Tag(i) => self.tags.get(i).map(|(name, _)| name.clone()),
+            _ => None,
+        };
+        self.tags = tag_counts(current.clone());
+        self.untagged = current
+            .filter(|item| item.tags.as_deref().unwrap_or_default().is_empty())
+            .count();
+        self.checked
+            .retain(|checked| self.tags.iter().any(|(name, _)| name == checked));
+        if let Some(name) = name {
+            self.selected = match self.tags.iter().position(|(tag, _)| *tag == name) {
+                Some(i) => SelectedTag::Tag(i),
+                None => SelectedTag::All,
+            };
+        }
+    }
+
+    fn titles(&self) -> Vec<String> {
+        let fixed = FIXED_TAGS.iter().map(|tag| match tag {
+            SelectedTag::All => " All ".to_string(),
+            SelectedTag::Recent => " Recent ".to_string(),
+            SelectedTag::Untagged => format!(" Untagged ({}) ", self.untagged),
+            _ => " Archived ".to_string(),
+        });
+        let tags = self.tags.iter().map(|(name, count)| {
+            let check = if self.checked.contains(name) {
+                "✓ "
+            } else {
+                ""
+            };
+            format!(" {}{} ({}) ", check, name, count)
+        });
+        fixed.chain(tags).collect()
+    }
+
+    fn position(&self) -> usize {
+        match self.selected {
+            SelectedTag::Tag(i) => i + FIXED_TAGS.len(),
+            selected => FIXED_TAGS.iter().position(|t| *t == selected).unwrap_or(0),
+        }
+    }
+
+    fn at_position(i: usize) -> SelectedTag {
+        match FIXED_TAGS.get(i) {
+            Some(tag) => *tag,
+            None => SelectedTag::Tag(i - FIXED_TAGS.len()),
+        }
+    }
+
+    // Tag under a column of the tags area, the same layout as `Tabs::render`
+    fn at(&self, column: u16) -> Option<SelectedTag> {
+        // Left border and padding
+        let mut x = 2;
+        for (i, title) in self.titles().iter().enumerate() {
+            let end = x + title.width() as u16;
+            if (x..end).contains(&column) {
+                return Some(Self::at_position(i));
             }
+            // Padding, divider and padding
+            x = end + 3;
         }
+        None
+    }
+
+    fn next(&mut self) {
+        let len = FIXED_TAGS.len() + self.tags.len();
+        self.selected = Self::at_position((self.position() + 1) % len);
     }
 
     fn prev(&mut self) {
-        self.selected = match self.selected {
-            SelectedTag::All => {
-                if self.tags.is_empty() {
-                    SelectedTag::Archive
-                } else {
-                    SelectedTag::Tag(self.tags.len() - 1)
-                }
+        let len = FIXED_TAGS.len() + self.tags.len();
+        self.selected = Self::at_position((self.position() + len - 1) % len);
+    }
+
+    // Checks or unchecks the tag under the cursor
+    fn toggle_checked(&mut self) -> Option<String> {
+        let SelectedTag::Tag(i) = self.selected else {
+            return None;
+        };
+        let name = self.tags[i].0.clone();
+        if !self.checked.remove(&name) {
+            self.checked.insert(name.clone());
+        }
+        Some(name)
+    }
+
+    // Checked tags apply on any tag tab, otherwise the tag under the cursor
+    fn matches(&self, item: &PasswordData) -> bool {
+        let archived = item.archive == Some(true);
+        let tags = item.tags.as_deref().unwrap_or_default();
+        match self.selected {
+            SelectedTag::All | SelectedTag::Recent => !archived,
+            SelectedTag::Untagged => !archived && tags.is_empty(),
+            SelectedTag::Archive => archived,
+            SelectedTag::Tag(_) if !self.checked.is_empty() => {
+                let mut checked = self.checked.iter();
+                let has = |name: &String| tags.contains(name);
+                !archived
+                    && if self.any {
+                        checked.any(has)
+                    } else {
+                        checked.all(has)
+                    }
             }
-            SelectedTag::Archive => SelectedTag::All,
-            SelectedTag::Tag(i) => {
-                if i == 0 {
-                    SelectedTag::Archive
-                } else {
-                    SelectedTag::Tag(i - 1)
-                }
+            SelectedTag::Tag(i) => !archived && tags.contains(&self.tags[i].0),
+        }
+    }
+
+    // "work" or "work AND home"
+    fn title(&self) -> String {
+        match self.selected {
+            SelectedTag::All => "All".to_string(),
+            SelectedTag::Recent => "Recent".to_string(),
+            SelectedTag::Untagged => "Untagged".to_string(),
+            SelectedTag::Archive => "Archive".to_string(),
+            SelectedTag::Tag(_) if !self.checked.is_empty() => {
+                let mode = if self.any { " OR " } else { " AND " };
+                self.checked
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(mode)
             }
+            SelectedTag::Tag(i) => self.tags[i].0.clone(),
         }
     }
 }
@@ -90,6 +725,8 @@ enum SelectedView {
 struct ListData<T> {
     state: ListState,
     items: Vec<T>,
+    // First visible item, `ListState` keeps its own private copy
+    offset: usize,
 }
 
 impl<T> ListData<T> {
@@ -97,7 +734,23 @@ impl<T> ListData<T> {
         Self {
             state: ListState::default(),
             items,
+            offset: 0,
+        }
+    }
+
+    // Same scrolling as `List` for items of `ITEM_HEIGHT` rows
+    fn scroll(&mut self, area: Rect) {
+        if self.items.is_empty() {
+            return;
         }
+        let visible = (area.height.saturating_sub(2) as usize / ITEM_HEIGHT).max(1);
+        let selected = self.state.selected().unwrap_or(0).min(self.items.len() - 1);
+        let offset = self.offset.min(self.items.len() - 1);
+        self.offset = if selected >= offset + visible {
+            selected + 1 - visible
+        } else {
+            offset.min(selected)
+        };
     }
 
     fn next(&mut self) {
@@ -137,8 +790,38 @@ impl<T> ListData<T> {
     fn unselect(&mut self) {
         self.state.select(None);
     }
+
+    fn first(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
+    fn last(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(self.items.len() - 1));
+        }
+    }
+
+    // Moves without wrapping around
+    fn forward(&mut self, n: usize) {
+        if !self.items.is_empty() {
+            let i = self.state.selected().map(|i| i + n).unwrap_or_default();
+            self.state.select(Some(i.min(self.items.len() - 1)));
+        }
+    }
+
+    fn back(&mut self, n: usize) {
+        if !self.items.is_empty() {
+            let i = self.state.selected().unwrap_or_default();
+            self.state.select(Some(i.saturating_sub(n)));
+        }
+    }
 }
 
+// Items moved by page up/down
+const PAGE: usize = 5;
+
 struct Log {
     time: String,
     message: String,
@@ -153,36 +836,56 @@ impl Log {
     }
 }
 
-pub fn start(mut passwords: Vec<PasswordData>) -> IoResult<()> {
-    passwords.sort_by(|a, b| {
-        let a = a.pin.unwrap_or_default();
-        let b = b.pin.unwrap_or_default();
-        b.cmp(&a)
-    });
+// Equal keys keep the vault order
+fn sort_passwords(passwords: &mut [PasswordData], sort: Sort, usage: &Usage) {
+    passwords.sort_by_key(|p| p.index);
+    match sort {
+        Sort::Pinned => passwords.sort_by_key(|p| Reverse(p.pin.unwrap_or_default())),
+        Sort::Name => passwords.sort_by_cached_key(|p| p.name(false).to_lowercase()),
+        Sort::Updated => passwords.sort_by_key(|p| Reverse(p.updated.unwrap_or_default())),
+        Sort::Copied => passwords.sort_by_key(|p| Reverse(usage.last(p))),
+    }
+}
 
-    let tags = passwords
-        .iter()
-        .flat_map(|item| item.tags.clone().unwrap_or_default())
-        .collect::<HashSet<String>>()
-        .into_iter()
-        .collect::<Vec<String>>();
+pub fn start(mut vault: Vault) -> IoResult<()> {
+    let settings = Settings::read();
+    let mut passwords = vault.passwords();
+    let offline = vault.offline();
+    let usage = Usage::load(&vault);
+    sort_passwords(&mut passwords, settings.sort, &usage);
 
-    let logs = vec![
+    let mut logs = vec![
         Log::new("Loading completed".to_string()),
         Log::new(format!("Total {} passwords", passwords.len())),
     ];
+    if offline.is_some() {
+        logs.push(Log::new(
+            "Server unreachable, using offline cache".to_string(),
+        ));
+    }
 
     let state = AppState {
+        vault,
+        tag: Tag::new(&passwords),
         passwords,
-        tag: Tag {
-            selected: SelectedTag::All,
-            tags,
-        },
         list: ListData::new(vec![]),
         preview: ListData::new(vec![]),
         view: SelectedView::List,
         logs,
         lock: Instant::now(),
+        offline,
+        history: false,
+        popup: Popup::None,
+        editor: None,
+        notes: None,
+        reveal: None,
+        keymap: Keymap::new(&settings.keymap, &settings.bindings),
+        theme: Theme::new(&settings.theme),
+        settings,
+        areas: Areas::default(),
+        click: None,
+        log_scroll: 0,
+        usage,
     };
 
     enable_raw_mode()?;
@@ -212,32 +915,112 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut state: AppState) -> io::R
 
         terminal.draw(|f| ui(f, &mut state))?;
 
-        if event::poll(Duration::from_secs(1))? {
-            if let Event::Key(key) = event::read()? {
+        // Short enough for the OTP countdown to drain smoothly
+        if event::poll(Duration::from_millis(200))? {
+            let read = event::read()?;
+            if let Event::Mouse(event) = read {
+                // The editor only takes keys
+                if state.editor.is_none() {
+                    state.lock = Instant::now();
+                    match state.popup {
+                        Popup::None => state.mouse(event),
+                        _ if matches!(event.kind, MouseEventKind::Down(_)) => state.close_popup(),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if let Event::Key(key) = read {
                 state.lock = Instant::now();
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('a') => {
+                if let Some(editor) = &mut state.editor {
+                    match editor.key(key) {
+                        EditorEvent::None => {}
+                        EditorEvent::Save => state.save_edit(),
+                        EditorEvent::Cancel => {
+                            state.editor = None;
+                            state.log("Edit cancelled".to_string());
+                        }
+                    }
+                    continue;
+                }
+                if state.notes.is_some() {
+                    state.notes_key(key);
+                    continue;
+                }
+                match state.popup {
+                    Popup::None => {}
+                    Popup::ConfirmQr(_) => {
+                        state.popup = Popup::None;
+                        if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                            state.show_qr();
+                        }
+                        continue;
+                    }
+                    Popup::Qr(..) => {
+                        state.close_popup();
+                        continue;
+                    }
+                }
+                let Some(action) = state.keymap.action(key) else {
+                    continue;
+                };
+                if state.view == SelectedView::List {
+                    state.history = false;
+                }
+                if action != Action::Reveal {
+                    state.reveal = None;
+                }
+                match action {
+                    Action::Quit => return Ok(()),
+                    Action::Reveal if state.view == SelectedView::Preview => state.toggle_reveal(),
+                    Action::OtpQr => state.confirm_qr(),
+                    Action::Open => state.open_link(),
+                    Action::Edit => {
+                        if let Some(data) = state.selected().cloned() {
+                            state.edit(data);
+                        }
+                    }
+                    Action::New => state.edit(PasswordData::default()),
+                    Action::Sort => state.cycle_sort(),
+                    Action::CheckTag => state.check_tag(),
+                    Action::TagMode => state.toggle_tag_mode(),
+                    Action::PrevTag => {
                         state.tag.prev();
                         state.view = SelectedView::List;
                         state.list.unselect();
                         state.preview.state.select(None);
                     }
-                    KeyCode::Char('d') => {
+                    Action::NextTag => {
                         state.tag.next();
                         state.view = SelectedView::List;
                         state.list.unselect();
                         state.preview.state.select(None);
                     }
-                    KeyCode::Down => match state.view {
+                    Action::Down => match state.view {
                         SelectedView::List => state.list.next(),
                         SelectedView::Preview => state.preview.next(),
                     },
-                    KeyCode::Up => match state.view {
+                    Action::Up => match state.view {
                         SelectedView::List => state.list.prev(),
                         SelectedView::Preview => state.preview.prev(),
                     },
-                    KeyCode::Left => {
+                    Action::First => match state.view {
+                        SelectedView::List => state.list.first(),
+                        SelectedView::Preview => state.preview.first(),
+                    },
+                    Action::Last => match state.view {
+                        SelectedView::List => state.list.last(),
+                        SelectedView::Preview => state.preview.last(),
+                    },
+                    Action::PageDown => match state.view {
+                        SelectedView::List => state.list.forward(PAGE),
+                        SelectedView::Preview => state.preview.forward(PAGE),
+                    },
+                    Action::PageUp => match state.view {
+                        SelectedView::List => state.list.back(PAGE),
+                        SelectedView::Preview => state.preview.back(PAGE),
+                    },
+                    Action::Left => {
                         if state.list.state.selected().is_some() {
                             match state.view {
                                 SelectedView::List => state.list.unselect(),
@@ -250,7 +1033,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut state: AppState) -> io::R
                             state.list.state.select(Some(0));
                         }
                     }
-                    KeyCode::Right => {
+                    Action::Right => {
                         if state.list.state.selected().is_some() {
                             match state.view {
                                 SelectedView::List => {
@@ -266,20 +1049,10 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut state: AppState) -> io::R
                             state.list.state.select(Some(0));
                         }
                     }
-                    KeyCode::Enter => {
-                        if state.view == SelectedView::Preview {
-                            if let Some(i) = state.preview.state.selected() {
-                                let item = &state.preview.items[i];
-                                if state.logs.len() > 5 {
-                                    state.logs.remove(0);
-                                }
-                                let msg = match copy_text(&item.copy_value) {
-                                    Ok(_) => format!("Copied '{}'", item.key),
-                                    Err(msg) => format!("Failed '{}'", msg),
-                                };
-                                state.logs.push(Log::new(msg));
-                            }
-                        }
+                    Action::Copy if state.view == SelectedView::Preview => state.activate(),
+                    Action::Copy if state.list.state.selected().is_some() => {
+                        state.view = SelectedView::Preview;
+                        state.preview.next();
                     }
                     _ => {}
                 }
@@ -288,135 +1061,378 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut state: AppState) -> io::R
     }
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, state: &mut AppState) {
+// Smallest terminal that can show the tags and one entry
+const MIN_WIDTH: u16 = 30;
+const MIN_HEIGHT: u16 = 8;
+// Narrower terminals show either the list or the selected entry
+const SPLIT_WIDTH: u16 = 80;
+// Rows kept for the list before the log and help panels are shown
+const MAIN_HEIGHT: u16 = 12;
+
+fn fits(size: Rect) -> bool {
+    size.width >= MIN_WIDTH && size.height >= MIN_HEIGHT
+}
+
+// Collapsed panes are None
+struct Panes {
+    tags: Rect,
+    list: Option<Rect>,
+    preview: Option<Rect>,
+    logs: Option<Rect>,
+    help: Option<Rect>,
+}
+
+fn panes(size: Rect, detail: bool, help_height: u16) -> Panes {
+    let remaining = size.height - 3;
+    let bottom_height = if remaining >= MAIN_HEIGHT + help_height {
+        help_height
+    } else if remaining >= MAIN_HEIGHT / 2 + 3 {
+        // A single log line
+        3
+    } else {
+        0
+    };
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
             [
                 Constraint::Length(3),
-                Constraint::Length(f.size().height - 11),
-                Constraint::Length(8),
+                Constraint::Length(remaining - bottom_height),
+                Constraint::Length(bottom_height),
             ]
             .as_ref(),
         )
-        .split(f.size());
-    let main = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
-        .split(layout[1]);
-    let bottom = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
-        .split(layout[2]);
-
-    let tags_area = layout[0];
-    let [list_area, preview_area] = [main[0], main[1]];
-    let [log_area, help_area] = [bottom[0], bottom[1]];
-
-    state.list.items = state
-        .passwords
-        .iter()
-        .filter(|item| match state.tag.selected {
-            SelectedTag::All => item.archive != Some(true),
-            SelectedTag::Archive => item.archive == Some(true),
-            SelectedTag::Tag(i) => {
-                if item.archive != Some(true) {
-                    let tag = state.tag.tags[i].clone();
-                    let tags = item.tags.clone().unwrap_or_default();
-                    return tags.contains(&tag);
-                }
-                false
-            }
-        })
-        .cloned()
-        .collect();
+        .split(size);
+
+    let wide = size.width >= SPLIT_WIDTH;
+    let (list, preview) = if wide {
+        let main = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(layout[1]);
+        (Some(main[0]), Some(main[1]))
+    } else if detail {
+        (None, Some(layout[1]))
+    } else {
+        (Some(layout[1]), None)
+    };
+
+    let (logs, help) = if bottom_height == help_height && wide {
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(layout[2]);
+        (Some(bottom[0]), Some(bottom[1]))
+    } else if bottom_height > 0 {
+        (Some(layout[2]), None)
+    } else {
+        (None, None)
+    };
+
+    Panes {
+        tags: layout[0],
+        list,
+        preview,
+        logs,
+        help,
+    }
+}
 
-    f.render_widget(draw_tags(&state.tag), tags_area);
+fn ui<B: Backend>(f: &mut Frame<B>, state: &mut AppState) {
+    let size = f.size();
+    if !fits(size) {
+        state.areas = Areas::default();
+        let text = format!(
+            "Terminal too small\n{}x{}, at least {}x{}",
+            size.width, size.height, MIN_WIDTH, MIN_HEIGHT
+        );
+        let notice = Paragraph::new(text)
+            .style(state.theme.text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        f.render_widget(notice, size);
+        return;
+    }
+
+    // The help panel grows with the keymap
+    let help_height = state.keymap.help().len() as u16 + 2;
+    let detail = state.view == SelectedView::Preview && state.list.state.selected().is_some();
+    let panes = panes(size, detail, help_height);
+
+    state.filter_list();
+
+    state.areas = Areas {
+        tags: panes.tags,
+        list: panes.list,
+        preview: None,
+        logs: panes.logs,
+    };
 
-    f.render_stateful_widget(
-        draw_list(&state.list.items, &state.tag),
-        list_area,
-        &mut state.list.state,
+    f.render_widget(
+        draw_tags(&state.tag, state.offline, &state.theme),
+        panes.tags,
     );
 
-    if let Some(i) = state.list.state.selected() {
-        state.preview.items = state.list.items[i].to_display_value();
-        let preview = draw_preview(&state.list.items[i]);
+    if let Some(list_area) = panes.list {
+        state.list.scroll(list_area);
+        f.render_stateful_widget(
+            draw_list(
+                &state.list.items,
+                &state.tag,
+                state.settings.sort,
+                &state.usage,
+                &state.theme,
+            ),
+            list_area,
+            &mut state.list.state,
+        );
+    }
+
+    if let (Some(i), Some(preview_area)) = (state.list.state.selected(), panes.preview) {
+        let data = &state.list.items[i];
+        state.preview.items = data.to_display_value(state.history);
+        // Hidden again after a while or when another row is selected
+        if let Some((i, time)) = state.reveal {
+            if state.preview.state.selected() != Some(i)
+                || time.elapsed().as_secs() >= state.settings.reveal_seconds
+            {
+                state.reveal = None;
+            }
+        }
+        let reveal = state.reveal.map(|(i, _)| i);
+        let colors = state.settings.reveal_colors;
+        let preview = draw_preview(
+            &data.name(false),
+            &state.preview.items,
+            reveal,
+            colors,
+            &state.theme,
+        );
         f.render_stateful_widget(preview, preview_area, &mut state.preview.state);
+        state.preview.scroll(preview_area);
+        state.areas.preview = Some(preview_area);
     }
 
-    f.render_widget(draw_logs(&state.logs), log_area);
+    if let Some(log_area) = panes.logs {
+        let rows = log_area.height.saturating_sub(2) as usize;
+        let end = state.logs.len() - state.log_scroll.min(state.logs.len());
+        let logs = &state.logs[end.saturating_sub(rows)..end];
+        f.render_widget(draw_logs(logs, state.log_scroll, &state.theme), log_area);
+    }
 
-    f.render_widget(draw_help(), help_area);
-}
+    if let Some(help_area) = panes.help {
+        let help = state.keymap.help();
+        let width = help.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0) as u16;
+        let widths = [Constraint::Length(width), Constraint::Min(20)];
+        f.render_widget(draw_help(help, &widths, &state.theme), help_area);
+    }
 
-fn draw_tags<'a>(tag: &Tag) -> Tabs<'a> {
-    let style = Style::default().fg(Color::White);
+    match &state.popup {
+        Popup::None => {}
+        Popup::ConfirmQr(name) => {
+            let text = format!(
+                "Show the one-time password seed of '{}' as a QR code?\n\nAnyone who can see the screen can copy it.\n\nY: show, any other key: cancel",
+                name
+            );
+            draw_popup(f, " Show QR code ", &text, state.theme.confirm);
+        }
+        Popup::Qr(name, qr) => {
+            let title = format!(" {}, press any key to close ", name);
+            draw_popup(f, &title, qr, state.theme.qr);
+        }
+    }
 
-    let mut tags = vec![
-        Spans::from(Span::styled(" All ", style)),
-        Spans::from(Span::styled(" Archived ", style)),
-    ];
-    tags.extend(
-        tag.tags
-            .iter()
-            .map(|tag| Spans::from(Span::styled(format!(" {} ", tag), style))),
-    );
+    if let Some(notes) = &mut state.notes {
+        notes.draw(f, &state.theme);
+    }
 
-    let selected = match tag.selected {
-        SelectedTag::All => 0,
-        SelectedTag::Archive => 1,
-        SelectedTag::Tag(i) => i + 2,
+    if let Some(editor) = &state.editor {
+        editor.draw(f, &state.theme);
+    }
+}
+
+// Centered box sized to the text, or a notice if it does not fit
+fn draw_popup<B: Backend>(f: &mut Frame<B>, title: &str, text: &str, style: Style) {
+    let size = f.size();
+    let width = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+    let height = text.lines().count() as u16 + 2;
+    let (text, width, height) = if width > size.width || height > size.height {
+        ("Terminal too small", 24.min(size.width), 3.min(size.height))
+    } else {
+        (text, width, height)
     };
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let paragraph = Paragraph::new(text)
+        .style(style)
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title.to_string()),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_tags<'a>(tag: &Tag, offline: Option<u64>, theme: &Theme) -> Tabs<'a> {
+    let style = theme.text;
+
+    let checked = tag.checked.len();
+    let tags = tag
+        .titles()
+        .into_iter()
+        .enumerate()
+        .map(|(i, title)| {
+            let checked = matches!(Tag::at_position(i), SelectedTag::Tag(j) if tag.checked.contains(&tag.tags[j].0));
+            let style = if checked { theme.key } else { style };
+            Spans::from(Span::styled(title, style))
+        })
+        .collect();
+
+    let selected = tag.position();
+
+    let mut title = vec![Span::raw(" Tags ")];
+    if checked > 0 {
+        let mode = if tag.any { "any" } else { "all" };
+        title.push(Span::styled(
+            format!("{} checked, match {} ", checked, mode),
+            theme.key,
+        ));
+    }
+    if let Some(synced) = offline {
+        title.push(Span::styled(
+            format!(
+                " Offline, read-only, last synced {} ",
+                HumanTime::from_duration_since_timestamp(synced)
+            ),
+            theme.alert,
+        ));
+    }
 
     Tabs::new(tags)
-        .block(Block::default().borders(Borders::ALL).title(" Tags "))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .select(selected)
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(54, 132, 246))
-                .add_modifier(Modifier::BOLD),
-        )
+        .highlight_style(theme.highlight)
 }
 
-fn draw_list<'a>(passwords: &[PasswordData], tag: &Tag) -> List<'a> {
+fn draw_list<'a>(
+    passwords: &[PasswordData],
+    tag: &Tag,
+    sort: Sort,
+    usage: &Usage,
+    theme: &Theme,
+) -> List<'a> {
+    let recent = tag.selected == SelectedTag::Recent;
     let items: Vec<ListItem> = passwords
         .iter()
         .map(|item| {
+            // Recent entries show what was copied and when
+            let detail = if recent {
+                usage.summary(item)
+            } else {
+                item.user()
+            };
             ListItem::new(vec![
-                Spans::from(Span::styled(
-                    item.name(true),
-                    Style::default().fg(Color::White),
-                )),
-                Spans::from(Span::styled(item.user(), Style::default().fg(Color::White))),
+                Spans::from(Span::styled(item.name(true), theme.text)),
+                Spans::from(Span::styled(detail, theme.muted)),
                 Spans::from(""),
             ])
         })
         .collect();
     let title = match tag.selected {
-        SelectedTag::All => " All ".to_string(),
-        SelectedTag::Archive => " Archive ".to_string(),
-        SelectedTag::Tag(i) => format!(" {} ", tag.tags[i]),
+        SelectedTag::Recent => " Recent, last used first ".to_string(),
+        _ => format!(" {}, {} ", tag.title(), sort.title()),
     };
 
     List::new(items)
         .block(Block::default().borders(Borders::ALL).title(title))
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(54, 132, 246))
-                .add_modifier(Modifier::BOLD),
-        )
+        .highlight_style(theme.highlight)
         .highlight_symbol(" ↪ ")
 }
 
-fn draw_preview(data: &PasswordData) -> List {
-    let items = data
-        .to_display_value()
-        .into_iter()
-        .map(|item| {
+// Seconds left when a code is shown as about to expire
+const OTP_EXPIRY: f64 = 5.0;
+const OTP_GAUGE_WIDTH: usize = 20;
+
+fn draw_value<'a>(item: &'a DisplayValue, theme: &Theme) -> Spans<'a> {
+    let timer = match &item.timer {
+        Some(timer) => timer,
+        None => return Spans::from(Span::styled(&item.value, theme.text)),
+    };
+    let expiring = timer.remaining < OTP_EXPIRY;
+    let style = if expiring {
+        theme.danger
+    } else if timer.remaining < timer.step as f64 / 3.0 {
+        theme.warn
+    } else {
+        theme.ok
+    };
+    let filled = (timer.remaining / timer.step as f64 * OTP_GAUGE_WIDTH as f64).ceil() as usize;
+    let filled = filled.min(OTP_GAUGE_WIDTH);
+
+    let mut spans = vec![
+        Span::styled(&item.value, style.add_modifier(Modifier::BOLD)),
+        Span::raw("  "),
+        Span::styled("█".repeat(filled), style),
+        Span::styled("░".repeat(OTP_GAUGE_WIDTH - filled), theme.muted),
+        Span::styled(format!(" {:>2}s", timer.remaining.ceil() as u64), style),
+    ];
+    if expiring {
+        spans.push(Span::styled(format!("  next {}", timer.next), theme.text));
+    }
+    Spans::from(spans)
+}
+
+// Digits and symbols stand out when reading a value aloud
+fn draw_revealed<'a>(value: &'a str, colors: bool, theme: &Theme) -> Spans<'a> {
+    if !colors {
+        return Spans::from(Span::styled(value, theme.text));
+    }
+    let spans = value
+        .chars()
+        .map(|c| {
+            let style = if c.is_ascii_digit() {
+                theme.digit
+            } else if c.is_alphabetic() {
+                theme.text
+            } else {
+                theme.symbol
+            };
+            Span::styled(c.to_string(), style)
+        })
+        .collect::<Vec<Span>>();
+    Spans::from(spans)
+}
+
+fn draw_preview<'a>(
+    name: &str,
+    values: &'a [DisplayValue],
+    reveal: Option<usize>,
+    colors: bool,
+    theme: &Theme,
+) -> List<'a> {
+    let items = values
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let value = if item.hidden && reveal == Some(i) {
+                draw_revealed(&item.copy_value, colors, theme)
+            } else {
+                draw_value(item, theme)
+            };
             ListItem::new(vec![
-                Spans::from(Span::styled(item.key, Style::default().fg(Color::White))),
-                Spans::from(Span::styled(item.value, Style::default().fg(Color::White))),
+                Spans::from(Span::styled(&item.key, theme.muted)),
+                value,
                 Spans::from(""),
             ])
         })
@@ -426,46 +1442,92 @@ fn draw_preview(data: &PasswordData) -> List {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(format!(" {} ", data.name(false))),
-        )
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(54, 132, 246))
-                .add_modifier(Modifier::BOLD),
+                .title(format!(" {} ", name)),
         )
+        .highlight_style(theme.highlight)
         .highlight_symbol(" ↪ ")
 }
 
-fn draw_logs<'a>(logs: &[Log]) -> List<'a> {
+fn draw_logs<'a>(logs: &[Log], scroll: usize, theme: &Theme) -> List<'a> {
     let items = logs
         .iter()
         .map(|log| {
-            ListItem::new(vec![Spans::from(Span::raw(format!(
-                "{} {}",
-                log.time, log.message
-            )))])
+            ListItem::new(vec![Spans::from(Span::styled(
+                format!("{} {}", log.time, log.message),
+                theme.text,
+            ))])
         })
         .collect::<Vec<ListItem>>();
 
-    List::new(items).block(Block::default().borders(Borders::ALL).title(" Logs "))
+    let title = match scroll {
+        0 => " Logs ".to_string(),
+        n => format!(" Logs ({} newer) ", n),
+    };
+    List::new(items).block(Block::default().borders(Borders::ALL).title(title))
 }
 
-fn draw_help<'a>() -> Table<'a> {
-    let rows = [
-        ("A/D", "Toggle tag"),
-        ("Up/Down", "Toggle selected"),
-        ("Left/Right", "Toggle list/password"),
-        ("Enter", "Copy value"),
-        ("Q", "Quit"),
-    ]
-    .map(|(name, info)| {
+fn draw_help<'a>(
+    help: Vec<(String, &'a str)>,
+    widths: &'a [Constraint],
+    theme: &Theme,
+) -> Table<'a> {
+    let rows = help.into_iter().map(|(name, info)| {
         Row::new(vec![
-            Cell::from(Span::styled(name, Style::default().fg(Color::LightCyan))),
-            Cell::from(Span::styled(info, Style::default().fg(Color::Gray))),
+            Cell::from(Span::styled(name, theme.key)),
+            Cell::from(Span::styled(info, theme.muted)),
         ])
     });
     Table::new(rows)
         .block(Block::default().borders(Borders::ALL).title(" Help "))
-        .widths(&[Constraint::Length(11), Constraint::Min(20)])
+        .widths(widths)
         .column_spacing(1)
 }
+
+fn contains(area: Rect, x: u16, y: u16) -> bool {
+    x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP_HEIGHT: u16 = 16;
+
+    #[test]
+    fn smallest_terminal() {
+        assert!(fits(Rect::new(0, 0, MIN_WIDTH, MIN_HEIGHT)));
+        assert!(!fits(Rect::new(0, 0, MIN_WIDTH - 1, MIN_HEIGHT)));
+        assert!(!fits(Rect::new(0, 0, MIN_WIDTH, MIN_HEIGHT - 1)));
+
+        let panes = panes(Rect::new(0, 0, MIN_WIDTH, MIN_HEIGHT), false, HELP_HEIGHT);
+        assert_eq!(panes.tags.height, 3);
+        assert_eq!(panes.list.map(|r| r.height), Some(MIN_HEIGHT - 3));
+        assert!(panes.preview.is_none() && panes.logs.is_none() && panes.help.is_none());
+    }
+
+    #[test]
+    fn narrow_terminals_show_one_pane() {
+        let narrow = Rect::new(0, 0, SPLIT_WIDTH - 1, 40);
+        let list = panes(narrow, false, HELP_HEIGHT);
+        assert_eq!(list.list.map(|r| r.width), Some(SPLIT_WIDTH - 1));
+        assert!(list.preview.is_none() && list.help.is_none());
+        let detail = panes(narrow, true, HELP_HEIGHT);
+        assert!(detail.list.is_none());
+        assert_eq!(detail.preview.map(|r| r.width), Some(SPLIT_WIDTH - 1));
+
+        let wide = panes(Rect::new(0, 0, SPLIT_WIDTH, 40), true, HELP_HEIGHT);
+        assert!(wide.list.is_some() && wide.preview.is_some() && wide.help.is_some());
+    }
+
+    #[test]
+    fn short_terminals_collapse_the_bottom() {
+        let height = |h| panes(Rect::new(0, 0, SPLIT_WIDTH, h), false, HELP_HEIGHT);
+        let full = height(3 + MAIN_HEIGHT + HELP_HEIGHT);
+        assert_eq!(full.help.map(|r| r.height), Some(HELP_HEIGHT));
+        let log = height(3 + MAIN_HEIGHT + HELP_HEIGHT - 1);
+        assert!(log.help.is_none());
+        assert_eq!(log.logs.map(|r| r.height), Some(3));
+        assert!(height(3 + MAIN_HEIGHT / 2 + 3).logs.is_some());
+        assert!(height(3 + MAIN_HEIGHT / 2 + 2).logs.is_none());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::*;
+use home_config::HomeConfig;
+use std::collections::BTreeMap;
+use time_humanize::HumanTime;
+
+// Entries kept, the least recently used are dropped first
+const USAGE_LIMIT: usize = 200;
+const USAGE_KEY: &str = "kwrap usage";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UsageEntry {
+    // Time of the last copy and the field that was copied
+    pub last: u64,
+    pub field: String,
+    pub count: u64,
+    // Time of the last copy of each field
+    pub fields: BTreeMap<String, u64>,
+}
+
+// Written next to `config.json`, the data is encrypted with a key of the vault
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    vault: String,
+    data: String,
+}
+
+// Copies of entries and fields, kept on this device only
+pub struct Usage {
+    vault: String,
+    cipher: Aes256Gcm,
+    entries: BTreeMap<String, UsageEntry>,
+}
+
+// The server id or the `uid` of a library entry, entries saved before `uid` use the name and user
+fn entry_id(data: &PasswordData) -> String {
+    match (&data.pid, &data.uid) {
+        (Some(id), _) | (None, Some(id)) => id.clone(),
+        (None, None) => format!("{}\n{}", data.name(false), data.user()),
+    }
+}
+
+impl Usage {
+    fn home_config() -> HomeConfig {
+        HomeConfig::new(env!("CARGO_PKG_NAME"), "usage.json")
+    }
+
+    // Starts empty when the file is missing, belongs to another vault or the key changed
+    pub fn load(vault: &Vault) -> Self {
+        let mut usage = Self {
+            vault: vault.id(),
+            cipher: Aes256Gcm::new(vault.subkey(USAGE_KEY)),
+            entries: BTreeMap::new(),
+        };
+        let Ok(file) = Self::home_config().json::<UsageFile>() else {
+            return usage;
+        };
+        if file.vault != usage.vault {
+            return usage;
+        }
+        let Ok(mut data) = base64::decode(&file.data) else {
+            return usage;
+        };
+        if let Ok(json) = usage.cipher.try_decrypt(&mut data, usage.vault.as_bytes()) {
+            usage.entries = serde_json::from_slice(json).unwrap_or_default();
+            json.zeroize();
+        }
+        usage
+    }
+
+    pub fn save(&self) {
+        let data = self
+            .cipher
+            .encrypt_from(&self.entries, self.vault.as_bytes());
+        let file = UsageFile {
+            vault: self.vault.clone(),
+            data: base64::encode(data),
+        };
+        let _ = Self::home_config().save_json(&file);
+    }
+
+    pub fn delete() -> std::io::Result<()> {
+        Self::home_config().delete()
+    }
+
+    pub fn record(&mut self, data: &PasswordData, field: &str) {
+        let now = timestamp();
+        let entry = self.entries.entry(entry_id(data)).or_default();
+        entry.last = now;
+        entry.field = field.to_string();
+        entry.count += 1;
+        entry.fields.insert(field.to_string(), now);
+
+        if self.entries.len() > USAGE_LIMIT {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = oldest {
+                self.entries.remove(&id);
+            }
+        }
+    }
+
+    pub fn get(&self, data: &PasswordData) -> Option<&UsageEntry> {
+        self.entries.get(&entry_id(data))
+    }
+
+    pub fn last(&self, data: &PasswordData) -> u64 {
+        self.get(data).map(|entry| entry.last).unwrap_or_default()
+    }
+
+    // Used entries, most recent first
+    pub fn recent<'a>(&self, passwords: &'a [PasswordData], limit: usize) -> Vec<&'a PasswordData> {
+        let mut recent = passwords
+            .iter()
+            .filter(|item| item.archive != Some(true) && self.get(item).is_some())
+            .collect::<Vec<&PasswordData>>();
+        recent.sort_by_key(|item| std::cmp::Reverse(self.last(item)));
+        recent.truncate(limit);
+        recent
+    }
+
+    // "Password, 5 minutes ago"
+    pub fn summary(&self, data: &PasswordData) -> String {
+        match self.get(data) {
+            Some(entry) => format!(
+                "{}, {}",
+                entry.field,
+                HumanTime::from_duration_since_timestamp(entry.last)
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_entries_keep_their_usage() {
+        let mut usage = Usage {
+            vault: String::new(),
+            cipher: Aes256Gcm::new([0; 32]),
+            entries: BTreeMap::new(),
+        };
+        let mut a = PasswordData::default();
+        a.name = Some("github".to_string());
+        a.uid = Some(new_uid());
+        let mut b = PasswordData::default();
+        b.name = Some("github".to_string());
+        b.uid = Some(new_uid());
+
+        usage.record(&a, "Password");
+        a.name = Some("GitHub".to_string());
+        assert_eq!(usage.get(&a).map(|entry| entry.count), Some(1));
+        assert!(usage.get(&b).is_none());
+    }
+}
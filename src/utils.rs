@@ -1,6 +1,11 @@
 use arboard::Clipboard;
+use reqwest::Url;
+use std::env;
 use std::fmt::{Debug, Display};
+use std::fs::{self, File};
+use std::io::{Result as IoResult, Write};
 use std::path::Path;
+use std::process::{Command, Stdio};
 use std::time::{SystemTime, UNIX_EPOCH};
 use time::OffsetDateTime;
 
@@ -22,6 +27,66 @@ pub fn copy_text<S: ToString>(text: S) -> Result<(), String> {
         .map_err(|err| err.to_string())
 }
 
+// Links saved without a scheme, such as "example.com" or "localhost:8080", are web links
+pub fn link_url(link: &str, schemes: &[String]) -> Result<Url, String> {
+    let link = link.trim();
+    let port = link
+        .split_once(':')
+        .is_some_and(|(_, rest)| rest.starts_with(|c: char| c.is_ascii_digit()));
+    let url = match Url::parse(link) {
+        Ok(url) if !port => Ok(url),
+        _ => Url::parse(&format!("https://{}", link)),
+    }
+    .map_err(|err| format!("Invalid link: {}", err))?;
+    if !schemes.iter().any(|s| s.eq_ignore_ascii_case(url.scheme())) {
+        return Err(format!(
+            "Links with the '{}' scheme are not opened",
+            url.scheme()
+        ));
+    }
+    Ok(url)
+}
+
+// `$BROWSER` is the first of a colon separated list, "%s" is replaced with the link
+fn browser_command(url: &str) -> Command {
+    if let Some(browser) = env::var("BROWSER").ok().filter(|b| !b.trim().is_empty()) {
+        let browser = browser.split(':').next().unwrap_or_default();
+        let mut parts = browser.split_whitespace();
+        let mut cmd = Command::new(parts.next().unwrap_or_default());
+        let args = parts.collect::<Vec<&str>>();
+        if args.iter().any(|arg| arg.contains("%s")) {
+            cmd.args(args.iter().map(|arg| arg.replace("%s", url)));
+        } else {
+            cmd.args(args).arg(url);
+        }
+        return cmd;
+    }
+    let mut cmd = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut cmd = Command::new("rundll32");
+        cmd.arg("url.dll,FileProtocolHandler");
+        cmd
+    } else {
+        Command::new("xdg-open")
+    };
+    cmd.arg(url);
+    cmd
+}
+
+// The link is passed as an argument, never through a shell
+pub fn open_link(link: &str, schemes: &[String]) -> Result<Url, String> {
+    let url = link_url(link, schemes)?;
+    let mut child = browser_command(url.as_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to start the browser: {}", err))?;
+    std::thread::spawn(move || child.wait());
+    Ok(url)
+}
+
 pub fn time_now() -> String {
     let now = OffsetDateTime::now_local();
     now.format("%H:%M:%S")
@@ -34,16 +99,24 @@ pub fn timestamp() -> u64 {
         .as_secs()
 }
 
+// Seconds with a fraction, for countdowns
+pub fn timestamp_f64() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_exit("Get system time")
+        .as_secs_f64()
+}
+
 pub fn decode_base64<T: AsRef<[u8]>>(input: T) -> Vec<u8> {
     base64::decode(input.as_ref()).unwrap_exit("base64 decoding failed")
 }
 
 pub fn trim_str<S: AsRef<str>>(s: S) -> String {
     let s = s.as_ref();
-    if s.len() > 32 {
-        format!("...{}", &s[(s.len() - 32)..])
-    } else {
-        s.to_string()
+    match s.char_indices().rev().nth(31) {
+        // The last 32 characters
+        Some((i, _)) if i > 0 => format!("...{}", &s[i..]),
+        _ => s.to_string(),
     }
 }
 
@@ -57,6 +130,23 @@ pub fn absolute_path(path: String) -> String {
     cur.join(p).display().to_string()
 }
 
+// Write to a temp file and rename it over `path`, optionally keeping the old file as `.bak`
+pub fn write_atomic<P: AsRef<Path>>(path: P, data: &[u8], backup: bool) -> IoResult<()> {
+    let path = path.as_ref();
+    let tmp = format!("{}.tmp", path.display());
+    let mut f = File::create(&tmp)?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    if backup && path.is_file() {
+        fs::copy(path, format!("{}.bak", path.display()))?;
+    }
+    fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent().and_then(|dir| File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
 pub trait ThrowError<T, E, M> {
     fn unwrap_exit(self, msg: M) -> T;
 }
@@ -70,3 +160,53 @@ where
         self.unwrap_or_else(|err| exit!("{}\n{:#?}", msg, err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemes() -> Vec<String> {
+        ["https", "http", "mailto"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn link_url_rejects_other_schemes() {
+        for link in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "file:///etc/passwd",
+            "FILE:///etc/passwd",
+            "data:text/html,<script>alert(1)</script>",
+        ] {
+            assert!(link_url(link, &schemes()).is_err(), "{}", link);
+        }
+    }
+
+    #[test]
+    fn link_url_opens_web_links() {
+        let url = link_url("HTTPS://Example.com/login", &schemes()).unwrap();
+        assert_eq!(url.as_str(), "https://example.com/login");
+        let url = link_url(" example.com ", &schemes()).unwrap();
+        assert_eq!(url.as_str(), "https://example.com/");
+        let url = link_url("localhost:8080", &schemes()).unwrap();
+        assert_eq!(
+            (url.host_str(), url.port()),
+            (Some("localhost"), Some(8080))
+        );
+        let url = link_url("mailto:alice@example.com", &schemes()).unwrap();
+        assert_eq!(url.scheme(), "mailto");
+    }
+
+    #[test]
+    fn trim_str_keeps_the_last_32_chars() {
+        assert_eq!(trim_str("short"), "short");
+        assert_eq!(trim_str("a".repeat(32)), "a".repeat(32));
+        assert_eq!(trim_str("b".repeat(33)), format!("...{}", "b".repeat(32)));
+        let cjk = "密码".repeat(20);
+        assert_eq!(trim_str(&cjk), format!("...{}", "密码".repeat(16)));
+        assert_eq!(
+            trim_str(format!("x{}", "é".repeat(32))),
+            format!("...{}", "é".repeat(32))
+        );
+    }
+}